@@ -1,12 +1,12 @@
 use std::error::Error;
 use std::path::Path;
 
-use symphonia::core::audio::{AudioBufferRef, SampleBuffer, Signal};
+use symphonia::core::audio::{AudioBufferRef, SampleBuffer};
 use symphonia::core::codecs::DecoderOptions;
 use symphonia::core::errors::Error as SymphoniaError;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
 use symphonia::core::probe::Hint;
 
 pub fn decode_audio_mono_f32(path: &Path) -> Result<(Vec<f32>, u32), Box<dyn Error>> {
@@ -70,6 +70,96 @@ pub fn decode_audio_mono_f32(path: &Path) -> Result<(Vec<f32>, u32), Box<dyn Err
     Ok((audio_buf, sample_rate))
 }
 
+/// Artist, title, genre and energy tags of an audio file, plus its length.
+#[derive(Debug, Clone, Default)]
+pub struct TrackTags {
+    pub artist: Option<String>,
+    pub title: Option<String>,
+    /// length in seconds, as declared by the container
+    pub duration: Option<f32>,
+    pub genres: Vec<String>,
+    /// between 0 and 1, from an energy level tag or comment
+    pub energy: Option<f32>,
+}
+
+pub fn read_tags(path: &Path) -> Result<TrackTags, Box<dyn Error>> {
+    let src = std::fs::File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(src), Default::default());
+    let hint = Hint::new();
+    let meta_opts: MetadataOptions = Default::default();
+    let fmt_opts: FormatOptions = Default::default();
+    let mut probed = symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)?;
+
+    let mut tags = TrackTags::default();
+    // ID3 tags are read while probing, container tags (FLAC, MP4) by the reader
+    if let Some(metadata) = probed.metadata.get() {
+        if let Some(revision) = metadata.current() {
+            collect_tags(revision, &mut tags);
+        }
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        collect_tags(revision, &mut tags);
+    }
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        if let (Some(frames), Some(sample_rate)) = (params.n_frames, params.sample_rate) {
+            tags.duration = Some(frames as f32 / sample_rate as f32);
+        }
+    }
+    Ok(tags)
+}
+
+fn collect_tags(revision: &MetadataRevision, tags: &mut TrackTags) {
+    for tag in revision.tags() {
+        let value = tag.value.to_string();
+        match tag.std_key {
+            Some(StandardTagKey::Artist) if tags.artist.is_none() => tags.artist = Some(value),
+            Some(StandardTagKey::TrackTitle) if tags.title.is_none() => tags.title = Some(value),
+            Some(StandardTagKey::Genre) => {
+                for genre in value.split([';', '/', ',']).map(str::trim) {
+                    if !genre.is_empty() && !tags.genres.iter().any(|known| known == genre) {
+                        tags.genres.push(genre.to_string());
+                    }
+                }
+            }
+            Some(StandardTagKey::Comment) if tags.energy.is_none() => {
+                tags.energy = energy_in_comment(&value);
+            }
+            None if tags.energy.is_none() && is_energy_tag(&tag.key) => {
+                tags.energy = energy_level(&value);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `key` names an energy level, e.g. `TXXX:EnergyLevel` or
+/// `ENERGY`.
+fn is_energy_tag(key: &str) -> bool {
+    let name = key.rsplit(':').next().unwrap_or(key).to_lowercase();
+    matches!(name.as_str(), "energy" | "energylevel" | "energy level")
+}
+
+/// The energy in a comment like `8A - Energy 6`, as written by Mixed In Key.
+fn energy_in_comment(comment: &str) -> Option<f32> {
+    let lower = comment.to_ascii_lowercase();
+    let rest = &comment[lower.find("energy")? + "energy".len()..];
+    energy_level(rest.split_whitespace().next()?)
+}
+
+/// An energy level between 0 and 1, or from 1 to 10 as DJ software tags it.
+fn energy_level(value: &str) -> Option<f32> {
+    let level: f32 = value.trim().parse().ok()?;
+    if !level.is_finite() || level < 0.0 {
+        return None;
+    }
+    Some(if level > 1.0 {
+        (level / 10.0).min(1.0)
+    } else {
+        level
+    })
+}
+
 fn to_mono_f32<'a>(
     buffer: &'a AudioBufferRef<'a>,
     sample_buf: &'a mut Option<SampleBuffer<f32>>,
@@ -2,14 +2,29 @@ use std::error::Error;
 use std::path::Path;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
 
 use sortlib::types::key::Key;
 
+/// Version of the analysis stored in the cache; caches written by an older
+/// one are cleared, since their rows lack what was added since.
+///
+/// 1: tempo
+/// 2: intro and outro keys
+/// 3: loudness
+const CACHE_VERSION: i32 = 3;
+
 #[derive(Debug, Clone)]
 pub struct KeyCacheEntry {
     pub key: Key,
     pub confidence: f32,
+    pub bpm: Option<f32>,
+    /// key of the opening seconds, see `crate::pipeline::SECTION_SECONDS`
+    pub intro_key: Option<Key>,
+    /// key of the closing seconds
+    pub outro_key: Option<Key>,
+    /// average loudness in dBFS
+    pub loudness: Option<f32>,
 }
 
 pub struct KeyCache {
@@ -22,16 +37,29 @@ impl KeyCache {
         conn.pragma_update(None, "journal_mode", "WAL")?;
         conn.pragma_update(None, "synchronous", "NORMAL")?;
         conn.busy_timeout(Duration::from_secs(5))?;
-        conn.execute_batch(
+        // tracks are analyzed in parallel, each opening the cache, so only one
+        // of them may migrate it
+        let tx = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate)?;
+        tx.execute_batch(
             "CREATE TABLE IF NOT EXISTS track_keys (
                 path TEXT PRIMARY KEY,
                 mtime INTEGER NOT NULL,
                 size INTEGER NOT NULL,
                 key TEXT NOT NULL,
                 key_confidence REAL NOT NULL,
-                analyzed_at INTEGER NOT NULL
+                analyzed_at INTEGER NOT NULL,
+                bpm REAL,
+                intro_key TEXT,
+                outro_key TEXT,
+                loudness REAL
             );",
         )?;
+        add_missing_column(&tx, "bpm", "REAL")?;
+        add_missing_column(&tx, "intro_key", "TEXT")?;
+        add_missing_column(&tx, "outro_key", "TEXT")?;
+        add_missing_column(&tx, "loudness", "REAL")?;
+        clear_outdated(&tx)?;
+        tx.commit()?;
         Ok(Self { conn })
     }
 
@@ -42,19 +70,43 @@ impl KeyCache {
         let row = self
             .conn
             .query_row(
-                "SELECT key, key_confidence, mtime, size FROM track_keys WHERE path = ?1",
+                "SELECT key, key_confidence, mtime, size, bpm, intro_key, outro_key, loudness
+                 FROM track_keys WHERE path = ?1",
                 params![path_key.as_ref()],
                 |row| {
                     let key: String = row.get(0)?;
                     let confidence: f64 = row.get(1)?;
                     let cached_mtime: i64 = row.get(2)?;
                     let cached_size: i64 = row.get(3)?;
-                    Ok((key, confidence, cached_mtime, cached_size))
+                    let bpm: Option<f64> = row.get(4)?;
+                    let intro_key: Option<String> = row.get(5)?;
+                    let outro_key: Option<String> = row.get(6)?;
+                    let loudness: Option<f64> = row.get(7)?;
+                    Ok((
+                        key,
+                        confidence,
+                        cached_mtime,
+                        cached_size,
+                        bpm,
+                        intro_key,
+                        outro_key,
+                        loudness,
+                    ))
                 },
             )
             .optional()?;
 
-        let Some((key_str, confidence, cached_mtime, cached_size)) = row else {
+        let Some((
+            key_str,
+            confidence,
+            cached_mtime,
+            cached_size,
+            bpm,
+            intro_key,
+            outro_key,
+            loudness,
+        )) = row
+        else {
             return Ok(None);
         };
 
@@ -63,9 +115,15 @@ impl KeyCache {
         }
 
         let key = Key::from_camelot(&key_str)?;
+        let section_key =
+            |value: Option<String>| value.and_then(|value| Key::from_camelot(&value).ok());
         Ok(Some(KeyCacheEntry {
             key,
             confidence: confidence as f32,
+            bpm: bpm.map(|bpm| bpm as f32),
+            intro_key: section_key(intro_key),
+            outro_key: section_key(outro_key),
+            loudness: loudness.map(|loudness| loudness as f32),
         }))
     }
 
@@ -75,22 +133,67 @@ impl KeyCache {
         let path_key = path.to_string_lossy();
         let key_str = entry.key.to_string();
         let confidence = entry.confidence as f64;
+        let bpm = entry.bpm.map(|bpm| bpm as f64);
+        let intro_key = entry.intro_key.map(|key| key.to_string());
+        let outro_key = entry.outro_key.map(|key| key.to_string());
+        let loudness = entry.loudness.map(|loudness| loudness as f64);
 
         self.conn.execute(
-            "INSERT INTO track_keys (path, mtime, size, key, key_confidence, analyzed_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "INSERT INTO track_keys
+                (path, mtime, size, key, key_confidence, analyzed_at, bpm, intro_key, outro_key,
+                 loudness)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
              ON CONFLICT(path) DO UPDATE SET
                 mtime = excluded.mtime,
                 size = excluded.size,
                 key = excluded.key,
                 key_confidence = excluded.key_confidence,
-                analyzed_at = excluded.analyzed_at",
-            params![path_key.as_ref(), mtime, size, key_str, confidence, analyzed_at],
+                analyzed_at = excluded.analyzed_at,
+                bpm = excluded.bpm,
+                intro_key = excluded.intro_key,
+                outro_key = excluded.outro_key,
+                loudness = excluded.loudness",
+            params![
+                path_key.as_ref(),
+                mtime,
+                size,
+                key_str,
+                confidence,
+                analyzed_at,
+                bpm,
+                intro_key,
+                outro_key,
+                loudness
+            ],
         )?;
         Ok(())
     }
 }
 
+/// Drops every row analyzed before `CACHE_VERSION`, so those tracks are
+/// analyzed again.
+fn clear_outdated(conn: &Connection) -> Result<(), Box<dyn Error>> {
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version < CACHE_VERSION {
+        conn.execute_batch("DELETE FROM track_keys;")?;
+        conn.pragma_update(None, "user_version", CACHE_VERSION)?;
+    }
+    Ok(())
+}
+
+/// Adds `column` to caches created before it existed.
+fn add_missing_column(conn: &Connection, column: &str, kind: &str) -> Result<(), Box<dyn Error>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('track_keys')")?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if !columns.iter().any(|name| name == column) {
+        let sql = format!("ALTER TABLE track_keys ADD COLUMN {column} {kind};");
+        conn.execute_batch(&sql)?;
+    }
+    Ok(())
+}
+
 fn file_signature(path: &Path) -> Result<(i64, i64), Box<dyn Error>> {
     let metadata = std::fs::metadata(path)?;
     let mtime = metadata
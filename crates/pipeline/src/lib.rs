@@ -1,3 +1,4 @@
 pub mod audio;
 pub mod cache;
 pub mod pipeline;
+pub mod playlist;
@@ -1,14 +1,360 @@
 use loggit::logger::set_log_level;
 use loggit::Level;
-use melodic_pipeline::pipeline::analyze_and_sort_tracks_with_cache;
-use sortlib::algorithm::melodic_sort;
+use melodic_pipeline::pipeline::analyze_tracks_with_cache;
+use melodic_pipeline::playlist::{read_m3u, write_m3u};
+use sortlib::algorithm::{
+    melodic_sort, ConfidenceWeights, HistoryWeights, Movement, MovementWeights,
+};
+use sortlib::bridge::{find_bridge, BridgeOptions};
+use sortlib::community::{suggest_crates, CrateOptions};
+use sortlib::evaluate::evaluate;
+use sortlib::graph::CompatibilityGraph;
+use sortlib::grow::grow_set;
+use sortlib::learn::{compare_profiles, MovementCounts, DEFAULT_SCALE};
+use sortlib::profiles::{Profiles, PROFILE_NAMES};
+use sortlib::rules::TransitionRules;
+use sortlib::schedule::{Blend, WeightSchedule};
+use sortlib::scorer::{scorer_by_name, WeightedScorer, SCORER_NAMES};
+use sortlib::shuffle::{harmonic_shuffle, ShuffleOptions};
+use sortlib::solver::{
+    solver_by_name, Constraints, KeylessPolicy, SortRequest, Transposition, SOLVER_NAMES,
+};
+use sortlib::suggest::{suggest_next, SuggestOptions};
+use sortlib::tempo::TempoShift;
 use sortlib::types::track::Track;
 
+const DEFAULT_TRANSPOSITION: Transposition = Transposition {
+    max_semitones: 1,
+    penalty_per_semitone: 10,
+};
+
+const DEFAULT_WILDCARD: KeylessPolicy = KeylessPolicy::Wildcard {
+    weight: 0,
+    max_bpm_diff: None,
+    max_energy_diff: None,
+};
+
+struct CliOptions {
+    solvers: Vec<String>,
+    limit: usize,
+    rules: Option<std::path::PathBuf>,
+    transposition: Option<Transposition>,
+    target_bpm: Option<f32>,
+    history: Option<HistoryWeights>,
+    confidence: Option<ConfidenceWeights>,
+    /// `None` until `--keyless` or one of the wildcard settings is given
+    keyless: Option<KeylessPolicy>,
+    constraints: Constraints,
+    now_playing: Option<String>,
+    played: Vec<String>,
+    check: bool,
+    learn: Vec<std::path::PathBuf>,
+    profile: String,
+    profiles: Option<std::path::PathBuf>,
+    graph: Option<std::path::PathBuf>,
+    graph_keys: bool,
+    crates: Option<std::path::PathBuf>,
+    bridge: Option<(String, String)>,
+    grow: Option<String>,
+    set_len: usize,
+    schedule: Option<Vec<(f32, String)>>,
+    blend: Blend,
+    shuffle: Option<usize>,
+    seed: Option<u64>,
+    scorers: Vec<String>,
+}
+
+impl CliOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = CliOptions {
+            solvers: vec!["beam".to_string()],
+            limit: 100,
+            rules: None,
+            transposition: None,
+            target_bpm: None,
+            history: None,
+            confidence: None,
+            keyless: None,
+            constraints: Constraints::default(),
+            now_playing: None,
+            played: Vec::new(),
+            check: false,
+            learn: Vec::new(),
+            profile: "default".to_string(),
+            profiles: None,
+            graph: None,
+            graph_keys: false,
+            crates: None,
+            bridge: None,
+            grow: None,
+            set_len: 20,
+            schedule: None,
+            blend: Blend::Phases,
+            shuffle: None,
+            seed: None,
+            scorers: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--solver" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    options.solvers = if value == "all" {
+                        SOLVER_NAMES.iter().map(|name| name.to_string()).collect()
+                    } else {
+                        value.split(',').map(|name| name.to_string()).collect()
+                    };
+                }
+                "--limit" => options.limit = next_value(&mut args, &arg)?,
+                "--profile" => options.profile = next_value(&mut args, &arg)?,
+                "--profiles" => options.profiles = Some(next_value(&mut args, &arg)?),
+                "--rules" => options.rules = Some(next_value(&mut args, &arg)?),
+                "--transpose" => {
+                    options
+                        .transposition
+                        .get_or_insert(DEFAULT_TRANSPOSITION)
+                        .max_semitones = next_value(&mut args, &arg)?;
+                }
+                "--transpose-penalty" => {
+                    options
+                        .transposition
+                        .get_or_insert(DEFAULT_TRANSPOSITION)
+                        .penalty_per_semitone = next_value(&mut args, &arg)?;
+                }
+                "--bpm" => {
+                    let bpm: f32 = next_value(&mut args, &arg)?;
+                    if !(bpm.is_finite() && bpm > 0.0) {
+                        return Err(format!(
+                            "invalid {arg} value {bpm}, expected a positive tempo"
+                        ));
+                    }
+                    options.target_bpm = Some(bpm);
+                }
+                "--history" => options.history = Some(HistoryWeights::default()),
+                "--confidence" => options.confidence = Some(ConfidenceWeights::default()),
+                "--max-same-key" => {
+                    options
+                        .history
+                        .get_or_insert_with(HistoryWeights::default)
+                        .max_same_key_run = Some(next_value(&mut args, &arg)?);
+                }
+                "--artist-gap" => options.constraints.artist_gap = next_value(&mut args, &arg)?,
+                "--max-per-artist" => {
+                    options.constraints.max_per_artist = Some(next_value(&mut args, &arg)?);
+                }
+                "--now-playing" => options.now_playing = Some(next_value(&mut args, &arg)?),
+                "--played" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    options.played = value.split(',').map(|name| name.to_string()).collect();
+                }
+                "--check" => options.check = true,
+                "--keyless" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    let policy = match value.as_str() {
+                        "exclude" => KeylessPolicy::Exclude,
+                        "append" => KeylessPolicy::Append,
+                        // keeps the wildcard settings given before
+                        "wildcard" => options
+                            .keyless
+                            .filter(|policy| matches!(policy, KeylessPolicy::Wildcard { .. }))
+                            .unwrap_or(DEFAULT_WILDCARD),
+                        other => return Err(format!("unknown --keyless policy {other}")),
+                    };
+                    if matches!(options.keyless, Some(KeylessPolicy::Wildcard { .. }))
+                        && !matches!(policy, KeylessPolicy::Wildcard { .. })
+                    {
+                        return Err(format!(
+                            "--keyless {value} conflicts with the wildcard settings"
+                        ));
+                    }
+                    options.keyless = Some(policy);
+                }
+                "--keyless-weight" => {
+                    let value = next_value(&mut args, &arg)?;
+                    if let KeylessPolicy::Wildcard { weight, .. } = wildcard(&mut options, &arg)? {
+                        *weight = value;
+                    }
+                }
+                "--keyless-bpm" => {
+                    let value = next_value(&mut args, &arg)?;
+                    if let KeylessPolicy::Wildcard { max_bpm_diff, .. } =
+                        wildcard(&mut options, &arg)?
+                    {
+                        *max_bpm_diff = Some(value);
+                    }
+                }
+                "--keyless-energy" => {
+                    let value = next_value(&mut args, &arg)?;
+                    if let KeylessPolicy::Wildcard {
+                        max_energy_diff, ..
+                    } = wildcard(&mut options, &arg)?
+                    {
+                        *max_energy_diff = Some(value);
+                    }
+                }
+                "--cyclic" => options.constraints.cyclic = true,
+                "--forbid" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    for name in value.split(',') {
+                        let movement = Movement::from_name(name)
+                            .ok_or(format!("unknown movement {name} for --forbid"))?;
+                        options.constraints.forbidden_movements.push(movement);
+                    }
+                }
+                "--graph" => options.graph = Some(next_value(&mut args, &arg)?),
+                "--graph-keys" => options.graph_keys = true,
+                "--crates" => options.crates = Some(next_value(&mut args, &arg)?),
+                "--bridge" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    let (from, to) = value
+                        .split_once(',')
+                        .ok_or("--bridge expects FROM,TO".to_string())?;
+                    options.bridge = Some((from.to_string(), to.to_string()));
+                }
+                "--grow" => options.grow = Some(next_value(&mut args, &arg)?),
+                "--len" => options.set_len = next_value(&mut args, &arg)?,
+                "--max-bpm-step" => {
+                    options.constraints.max_bpm_step = Some(next_value(&mut args, &arg)?);
+                }
+                "--max-energy-step" => {
+                    options.constraints.max_energy_step = Some(next_value(&mut args, &arg)?);
+                }
+                "--schedule" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    let mut points = Vec::new();
+                    for point in value.split(',') {
+                        let (name, progress) = point
+                            .split_once(':')
+                            .ok_or(format!("--schedule expects PROFILE:PROGRESS, got {point}"))?;
+                        let progress = progress
+                            .parse()
+                            .map_err(|_| format!("invalid --schedule progress {progress}"))?;
+                        points.push((progress, name.to_string()));
+                    }
+                    options.schedule = Some(points);
+                }
+                "--blend" => options.blend = Blend::Interpolate,
+                "--shuffle" => options.shuffle = Some(next_value(&mut args, &arg)?),
+                "--seed" => options.seed = Some(next_value(&mut args, &arg)?),
+                "--scorer" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    for name in value.split(',') {
+                        if scorer_by_name(name).is_none() {
+                            return Err(format!(
+                                "unknown scorer {name}, expected one of {}",
+                                SCORER_NAMES.join(", ")
+                            ));
+                        }
+                        options.scorers.push(name.trim().to_ascii_lowercase());
+                    }
+                }
+                "--learn" => {
+                    let value: String = next_value(&mut args, &arg)?;
+                    options.learn = value.split(',').map(std::path::PathBuf::from).collect();
+                }
+                other => return Err(format!("unknown argument {other}")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The wildcard policy `flag` tunes, the policy when no other was given.
+fn wildcard<'o>(options: &'o mut CliOptions, flag: &str) -> Result<&'o mut KeylessPolicy, String> {
+    match options.keyless.get_or_insert(DEFAULT_WILDCARD) {
+        wildcard @ KeylessPolicy::Wildcard { .. } => Ok(wildcard),
+        _ => Err(format!("{flag} only applies to --keyless wildcard")),
+    }
+}
+
+/// Whether any track carries what the built-in scorer `name` compares.
+fn scorer_has_data(name: &str, tracks: &[Track]) -> bool {
+    tracks.iter().any(|track| match name {
+        "tempo" => track.bpm().is_some(),
+        "energy" => track.energy().is_some(),
+        "loudness" => track.loudness().is_some(),
+        "genre" => !track.genres().is_empty(),
+        _ => true,
+    })
+}
+
+fn next_value<T: std::str::FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> Result<T, String> {
+    let value = args.next().ok_or(format!("{flag} expects a value"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid {flag} value {value}"))
+}
+
 fn main() {
-    sort_jan_2026();
+    let options = match CliOptions::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}");
+            eprintln!(
+                "usage: melodic-pipeline [--solver {}|all] [--limit N] [--rules FILE.toml] \
+                 [--profile {}|NAME] [--profiles FILE.toml|FILE.json] \
+                 [--transpose N] [--transpose-penalty P] [--bpm TARGET] \
+                 [--history] [--confidence] [--max-same-key N] \
+                 [--artist-gap N] [--max-per-artist N] [--cyclic] [--forbid MOVEMENT,...] \
+                 [--keyless exclude|append|wildcard] \
+                 [--keyless-weight W] [--keyless-bpm MAX_DIFF] [--keyless-energy MAX_DIFF] \
+                 [--now-playing NAME [--played NAME,...]] [--check] \
+                 [--graph FILE.dot|FILE.graphml|FILE.json [--graph-keys]] \
+                 [--crates DIR] [--bridge FROM,TO] [--grow SEED [--len N]] \
+                 [--max-bpm-step BPM] [--max-energy-step E] \
+                 [--schedule PROFILE:PROGRESS,... [--blend]] [--shuffle N [--seed S]] \
+                 [--scorer {},...] [--learn SET.m3u,...]",
+                SOLVER_NAMES.join("|"),
+                PROFILE_NAMES.join("|"),
+                SCORER_NAMES.join("|")
+            );
+            std::process::exit(2);
+        }
+    };
+    if !options.learn.is_empty() {
+        learn_from_sets(&options.learn);
+        return;
+    }
+    sort_jan_2026(&options);
 }
 
-fn sort_jan_2026() {
+fn learn_from_sets(playlists: &[std::path::PathBuf]) {
+    let cache_path = std::path::PathBuf::from("melodic_cache.sqlite");
+    let mut counts = MovementCounts::default();
+    for playlist in playlists {
+        let paths = match read_m3u(playlist) {
+            Ok(paths) => paths,
+            Err(err) => {
+                eprintln!("{}: {err}", playlist.display());
+                continue;
+            }
+        };
+        counts.add_set(&analyze_tracks_with_cache(&paths, Some(&cache_path)));
+    }
+
+    let learned = counts.fit(DEFAULT_SCALE);
+    for movement in Movement::BUILT_IN {
+        println!(
+            "{} | played {} | weight {}",
+            movement,
+            counts.count(movement),
+            learned.weight(movement)
+        );
+    }
+    println!("clashes={} unknown={}", counts.clashes, counts.unknown);
+    let default = MovementWeights::default();
+    let profiles = [("learned", &learned), ("default", &default)];
+    for profile in compare_profiles(&counts, &profiles, DEFAULT_SCALE) {
+        println!(
+            "{}: log_likelihood={:.3} mean_weight={:.1} coverage={:.2}",
+            profile.name, profile.fit.log_likelihood, profile.fit.mean_weight, profile.fit.coverage
+        );
+    }
+}
+
+fn sort_jan_2026(options: &CliOptions) {
     let track_paths: Vec<std::path::PathBuf> = vec![
         "/Users/dobbikov/Desktop/djmusic/1-3xil3-Outblow-7BY1IX.mp3",
         "/Users/dobbikov/Desktop/djmusic/1-A-M-C-Bass--Teddy-Kil-TGOTOI.mp3",
@@ -332,20 +678,332 @@ fn sort_jan_2026() {
         "/Users/dobbikov/Desktop/djmusic/receptor - Lullaby Original Mix.mp3",
     ]
     .into_iter()
-    .map(|p| std::path::PathBuf::from(p))
+    .map(std::path::PathBuf::from)
     .collect();
 
     let cache_path = std::path::PathBuf::from("melodic_cache.sqlite");
-    let sorted = analyze_and_sort_tracks_with_cache(&track_paths, 100, &cache_path);
+    let tracks = analyze_tracks_with_cache(&track_paths, Some(&cache_path));
+    if options.constraints.max_energy_step.is_some()
+        && tracks.iter().all(|track| track.energy().is_none())
+    {
+        eprintln!("--max-energy-step has no effect, no track is tagged with an energy level");
+    }
+    if matches!(
+        options.keyless,
+        Some(KeylessPolicy::Wildcard {
+            max_energy_diff: Some(_),
+            ..
+        })
+    ) && tracks.iter().all(|track| track.energy().is_none())
+    {
+        eprintln!("--keyless-energy leaves out every keyless track, no track has an energy level");
+    }
+
+    let profiles = match &options.profiles {
+        Some(path) => Profiles::load(path),
+        None => Ok(Profiles::builtin()),
+    };
+    let profiles = match profiles {
+        Ok(profiles) => profiles,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let weights = match profiles.get(&options.profile) {
+        Ok(weights) => weights.clone(),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let schedule = options.schedule.as_ref().map(|points| {
+        let points: Vec<(f32, &str)> = points
+            .iter()
+            .map(|(progress, name)| (*progress, name.as_str()))
+            .collect();
+        WeightSchedule::from_profiles(&profiles, &points, options.blend)
+    });
+    let schedule = match schedule.transpose() {
+        Ok(schedule) => schedule,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    let rules = match &options.rules {
+        Some(path) => match TransitionRules::load(path, &weights) {
+            Ok(rules) => rules,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(2);
+            }
+        },
+        None => TransitionRules::camelot(&weights),
+    };
+    let mut scorer = WeightedScorer::new();
+    for name in &options.scorers {
+        if !scorer_has_data(name, &tracks) {
+            eprintln!("--scorer {name} has no effect, no track carries its {name}");
+        }
+        if let Some(part) = scorer_by_name(name) {
+            scorer = scorer.with_boxed(1.0, part);
+        }
+    }
+    let mut request = SortRequest::new(&tracks, &weights);
+    request.rules = Some(&rules);
+    if !options.scorers.is_empty() {
+        request.scorer = Some(&scorer);
+    }
+    request.schedule = schedule;
+    request.transposition = options.transposition;
+    request.target_bpm = options.target_bpm;
+    request.history = options.history.clone();
+    request.confidence = options.confidence;
+    request.keyless = options.keyless.unwrap_or_default();
+    request.constraints = options.constraints.clone();
+    request.budget.beam_width = options.limit;
+
+    if let Some(path) = &options.graph {
+        export_graph(&request, path, options.graph_keys);
+        return;
+    }
+    if let Some(dir) = &options.crates {
+        export_crates(&tracks, &weights, dir);
+        return;
+    }
+    if let Some((from, to)) = &options.bridge {
+        print_bridge(&request, from, to);
+        return;
+    }
+    if let Some(seed) = &options.grow {
+        let Some(seed) = find_track(&tracks, seed) else {
+            eprintln!("no track matches {seed}");
+            return;
+        };
+        let set = grow_set(&request, seed, options.set_len);
+        for (position, track) in set.tracks(&tracks).iter().enumerate() {
+            let key = set.keys[position].map_or("?".to_string(), |key| key.to_string());
+            println!("{} | {} | {}", position + 1, key, track.name());
+        }
+        for line in set.explain(Some(&rules)) {
+            println!("  {line}");
+        }
+        println!("grow: tracks={} score={}", set.len(), set.score);
+        return;
+    }
+    if let Some(count) = options.shuffle {
+        print_shuffle(&request, count, options.seed);
+        return;
+    }
+    if let Some(now_playing) = &options.now_playing {
+        print_suggestions(&request, now_playing, &options.played);
+        return;
+    }
+    if options.check {
+        let evaluation = evaluate(&request);
+        for line in evaluation.explain(Some(&rules)) {
+            println!("  {line}");
+        }
+        println!(
+            "clashes={} score={} per_transition={:.2}",
+            evaluation.clashes().count(),
+            evaluation.score,
+            evaluation.normalized
+        );
+        return;
+    }
 
-    let mut num = 1;
-    for track in sorted {
-        println!("{} | {} | {}", num, track.key().unwrap(), track.name());
-        num += 1
+    for name in &options.solvers {
+        let Some(solver) = solver_by_name(name) else {
+            eprintln!(
+                "unknown solver {name}, expected one of {}",
+                SOLVER_NAMES.join(", ")
+            );
+            continue;
+        };
+        let result = solver.solve(&request);
+        for (position, track) in result.tracks(&tracks).iter().enumerate() {
+            let Some(key) = track.key() else {
+                println!("{} | ? | {}", position + 1, track.name());
+                continue;
+            };
+            let played = result.keys[position].unwrap();
+            let mut notes = Vec::new();
+            if let Some(shift) = options
+                .target_bpm
+                .and_then(|target_bpm| TempoShift::of(track, target_bpm))
+            {
+                notes.push(format!(
+                    "@{:.1} BPM {:+.0} ct",
+                    shift.played_bpm,
+                    shift.semitones * 100.0
+                ));
+            }
+            if result.shifts[position] != 0 {
+                notes.push(format!("{:+} st", result.shifts[position]));
+            }
+            if notes.is_empty() {
+                println!("{} | {} | {}", position + 1, key, track.name());
+            } else {
+                println!(
+                    "{} | {} ({} -> {}) | {}",
+                    position + 1,
+                    key,
+                    notes.join(", "),
+                    played,
+                    track.name()
+                );
+            }
+        }
+        for line in result.explain(Some(&rules)) {
+            println!("  {line}");
+        }
+        for keyless in &result.keyless {
+            println!(
+                "no key: {} ({:?})",
+                tracks[keyless.track].name(),
+                keyless.handling
+            );
+        }
+        println!(
+            "solver={} tracks={} score={}",
+            result.solver,
+            result.len(),
+            result.score
+        );
+    }
+}
+
+/// Writes the compatibility graph of `request`, in the format the extension
+/// of `path` names.
+fn export_graph(request: &SortRequest, path: &std::path::Path, by_key: bool) {
+    let mut graph = CompatibilityGraph::new(request);
+    if by_key {
+        graph = graph.by_key();
+    }
+    let text = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("dot") | Some("gv") => graph.to_dot(),
+        Some("graphml") => graph.to_graphml(),
+        Some("json") => graph.to_json(),
+        _ => {
+            eprintln!("--graph expects a .dot, .graphml or .json file");
+            return;
+        }
+    };
+    match std::fs::write(path, text) {
+        Ok(()) => println!(
+            "graph: {} nodes, {} edges -> {}",
+            graph.nodes.len(),
+            graph.edges.len(),
+            path.display()
+        ),
+        Err(err) => eprintln!("{}: {err}", path.display()),
+    }
+}
+
+/// Writes one playlist per suggested crate into `dir`.
+fn export_crates(tracks: &[Track], weights: &MovementWeights, dir: &std::path::Path) {
+    if let Err(err) = std::fs::create_dir_all(dir) {
+        eprintln!("{}: {err}", dir.display());
+        return;
+    }
+    let crates = suggest_crates(tracks, weights, &CrateOptions::default());
+    for (num, suggested) in (1..).zip(&crates) {
+        let label = suggested.label();
+        let path = dir.join(format!("{num:02} {}.m3u", label.replace(" | ", " ")));
+        if let Err(err) = write_m3u(&path, &suggested.of(tracks)) {
+            eprintln!("{}: {err}", path.display());
+            continue;
+        }
+        println!("{} | {} tracks | {}", num, suggested.len(), label);
     }
 }
+
+fn print_bridge(request: &SortRequest, from: &str, to: &str) {
+    let tracks = request.tracks;
+    let (Some(from), Some(to)) = (find_track(tracks, from), find_track(tracks, to)) else {
+        eprintln!("no track matches {from} or {to}");
+        return;
+    };
+    let Some(bridge) = find_bridge(request, from, to, &BridgeOptions::default()) else {
+        println!(
+            "no bridge from {} to {}",
+            tracks[from].name(),
+            tracks[to].name()
+        );
+        return;
+    };
+    for (position, track) in bridge.tracks(tracks).iter().enumerate() {
+        let key = bridge.keys[position].map_or("?".to_string(), |key| key.to_string());
+        println!("{} | {} | {}", position + 1, key, track.name());
+    }
+    for line in bridge.explain(request.rules) {
+        println!("  {line}");
+    }
+    println!("bridge: tracks={} score={}", bridge.len(), bridge.score);
+}
+
+fn print_shuffle(request: &SortRequest, count: usize, seed: Option<u64>) {
+    let seed = seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    let options = ShuffleOptions {
+        seed,
+        ..ShuffleOptions::default()
+    };
+    let mut shuffle = harmonic_shuffle(request, options);
+    for (num, step) in (1..).zip(shuffle.by_ref().take(count)) {
+        let track = &request.tracks[step.track];
+        let movement = step
+            .movement
+            .map_or("jump".to_string(), |movement| movement.to_string());
+        println!("{} | {} | {}", num, movement, track.name());
+    }
+    println!("shuffle: seed={} jumps={}", seed, shuffle.jumps());
+}
+
+fn find_track(tracks: &[Track], name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    tracks
+        .iter()
+        .position(|track| track.name().to_lowercase().contains(&name))
+}
+
+fn print_suggestions(request: &SortRequest, now_playing: &str, played: &[String]) {
+    let tracks = request.tracks;
+    let Some(current) = find_track(tracks, now_playing) else {
+        eprintln!("no track matches {now_playing}");
+        return;
+    };
+    let played: Vec<usize> = played
+        .iter()
+        .filter_map(|name| find_track(tracks, name))
+        .collect();
+
+    let suggestions = suggest_next(request, current, &played, &SuggestOptions::default());
+    println!("now playing: {}", tracks[current].name());
+    for (num, suggestion) in (1..).zip(suggestions) {
+        let key = tracks[suggestion.track]
+            .key()
+            .map_or("?".to_string(), |key| key.to_string());
+        println!(
+            "{} | {} | {} ({:+}) | reachable={} | {}",
+            num,
+            key,
+            suggestion.movement,
+            suggestion.weight,
+            suggestion.reachable,
+            tracks[suggestion.track].name()
+        );
+    }
+}
+
+#[allow(dead_code)]
 fn sort_my_old_tracks() {
-    set_log_level(Level::DEBUG);
+    let _ = set_log_level(Level::DEBUG);
     let tracks = vec![
         Track::from_pair("One - Akov, VEGAS.mp3", "5A"),
         Track::from_pair("Decisions   Phace   Mefjus.mp3", "6A"),
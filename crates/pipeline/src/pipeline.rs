@@ -4,12 +4,16 @@ use loggit::{info, warn};
 use rayon::prelude::*;
 use stratum_dsp::{analyze_audio, AnalysisConfig};
 
-use crate::audio::decode_audio_mono_f32;
+use crate::audio::{decode_audio_mono_f32, read_tags};
 use crate::cache::{KeyCache, KeyCacheEntry};
 use sortlib::algorithm::melodic_sort;
 use sortlib::types::key::{Key, KeyLetter};
 use sortlib::types::track::Track;
 
+/// Seconds at each end of a track analyzed on their own for its intro and
+/// outro keys.
+pub const SECTION_SECONDS: f32 = 30.0;
+
 pub fn analyze_tracks<P: AsRef<Path> + Sync>(paths: &[P]) -> Vec<Track> {
     analyze_tracks_with_cache(paths, None)
 }
@@ -32,6 +36,10 @@ pub fn analyze_tracks_with_cache_mode<P: AsRef<Path> + Sync>(
     cache_path: Option<&Path>,
     mode: ProcessingMode,
 ) -> Vec<Track> {
+    // migrated once up front rather than by the first workers at the same time
+    if let Some(Err(err)) = cache_path.map(KeyCache::open) {
+        warn!("analyze_tracks: cache open failed ({})", err);
+    }
     let mut tracks: Vec<(usize, Track)> = match mode {
         ProcessingMode::Parallel => paths
             .par_iter()
@@ -93,6 +101,32 @@ fn stratum_key_to_camelot(value: stratum_dsp::Key) -> Result<Key, String> {
     Key::new(number, letter).map_err(|err| err.to_string())
 }
 
+/// Keys of the first and last `SECTION_SECONDS`, `None` for tracks too short
+/// to have separate sections or when the analysis fails.
+fn section_keys(samples: &[f32], sample_rate: u32) -> (Option<Key>, Option<Key>) {
+    let len = (SECTION_SECONDS * sample_rate as f32) as usize;
+    if len == 0 || samples.len() < 2 * len {
+        return (None, None);
+    }
+    let key_of = |section: &[f32]| {
+        let result = analyze_audio(section, sample_rate, AnalysisConfig::default()).ok()?;
+        stratum_key_to_camelot(result.key).ok()
+    };
+    (
+        key_of(&samples[..len]),
+        key_of(&samples[samples.len() - len..]),
+    )
+}
+
+/// Average loudness of `samples` in dBFS, `None` for silence.
+fn loudness(samples: &[f32]) -> Option<f32> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mean_square = samples.iter().map(|s| (s * s) as f64).sum::<f64>() / samples.len() as f64;
+    (mean_square > 0.0).then(|| (10.0 * mean_square.log10()) as f32)
+}
+
 fn analyze_one_track(idx: usize, path: &Path, cache_path: Option<&Path>) -> (usize, Track) {
     let path_str = path.to_string_lossy();
     info!("analyze_tracks: analyzing {}", path_str);
@@ -105,25 +139,38 @@ fn analyze_one_track(idx: usize, path: &Path, cache_path: Option<&Path>) -> (usi
         }
     });
 
-    let cached_key = cache.as_ref().and_then(|cache| match cache.get_cached_key(path) {
-        Ok(value) => value.map(|entry| entry.key),
+    let cached = cache.as_ref().and_then(|cache| match cache.get_cached_key(path) {
+        Ok(value) => value,
         Err(err) => {
             warn!("analyze_tracks: cache lookup failed for {} ({})", path_str, err);
             None
         }
     });
 
-    let key = if cached_key.is_some() {
-        cached_key
+    let mut bpm = cached.as_ref().and_then(|entry| entry.bpm);
+    let mut confidence = cached.as_ref().map(|entry| entry.confidence);
+    let mut intro_key = cached.as_ref().and_then(|entry| entry.intro_key);
+    let mut outro_key = cached.as_ref().and_then(|entry| entry.outro_key);
+    let mut track_loudness = cached.as_ref().and_then(|entry| entry.loudness);
+    let key = if let Some(entry) = cached {
+        Some(entry.key)
     } else {
         match decode_audio_mono_f32(path) {
             Ok((samples, sample_rate)) => match analyze_audio(&samples, sample_rate, AnalysisConfig::default()) {
                 Ok(result) => match stratum_key_to_camelot(result.key) {
                     Ok(key) => {
+                        bpm = (result.bpm > 0.0).then_some(result.bpm);
+                        confidence = Some(result.key_confidence);
+                        (intro_key, outro_key) = section_keys(&samples, sample_rate);
+                        track_loudness = loudness(&samples);
                         if let Some(cache) = cache.as_ref() {
                             let entry = KeyCacheEntry {
-                                key: key.clone(),
+                                key,
                                 confidence: result.key_confidence,
+                                bpm,
+                                intro_key,
+                                outro_key,
+                                loudness: track_loudness,
                             };
                             if let Err(err) = cache.store_key(path, &entry) {
                                 warn!("analyze_tracks: cache store failed for {} ({})", path_str, err);
@@ -160,8 +207,46 @@ fn analyze_one_track(idx: usize, path: &Path, cache_path: Option<&Path>) -> (usi
         .unwrap_or("unknown")
         .to_string();
 
-    (
-        idx,
-        Track::new(Some(idx as i32), name, path.to_path_buf(), key),
-    )
+    let tags = read_tags(path).unwrap_or_else(|err| {
+        warn!("analyze_tracks: tags failed for {} ({})", path_str, err);
+        Default::default()
+    });
+    // untagged files are often named "Artist - Title"
+    let (file_artist, file_title) = match name.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.to_string()), title.to_string()),
+        None => (None, name.clone()),
+    };
+    let duration = tags.duration;
+    let genres = tags.genres;
+    let energy = tags.energy;
+    let credit = tags.artist.or(file_artist);
+    let title = tags.title.unwrap_or(file_title);
+
+    let mut track = Track::new(Some(idx as i32), name, path.to_path_buf(), key);
+    if let Some(confidence) = confidence {
+        track = track.with_key_confidence(confidence);
+    }
+    if let Some(intro_key) = intro_key {
+        track = track.with_intro_key(intro_key);
+    }
+    if let Some(outro_key) = outro_key {
+        track = track.with_outro_key(outro_key);
+    }
+    if let Some(bpm) = bpm {
+        track = track.with_bpm(bpm);
+    }
+    if let Some(duration) = duration {
+        track = track.with_duration(duration);
+    }
+    if let Some(energy) = energy {
+        track = track.with_energy(energy);
+    }
+    if let Some(loudness) = track_loudness {
+        track = track.with_loudness(loudness);
+    }
+    if let Some(credit) = credit {
+        track = track.with_credit(&credit, &title);
+    }
+    track = track.with_genres(genres);
+    (idx, track)
 }
@@ -0,0 +1,31 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use sortlib::types::track::Track;
+
+/// Reads the track paths of an M3U playlist, resolving relative entries
+/// against the playlist's directory.
+pub fn read_m3u(path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let text = std::fs::read_to_string(path)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| base.join(line))
+        .collect())
+}
+
+/// Writes `tracks` as an extended M3U playlist, in order.
+pub fn write_m3u(path: &Path, tracks: &[Track]) -> Result<(), Box<dyn Error>> {
+    let mut text = String::from("#EXTM3U\n");
+    for track in tracks {
+        let seconds = track
+            .duration()
+            .map_or(-1, |duration| duration.round() as i64);
+        text.push_str(&format!("#EXTINF:{seconds},{}\n", track.name()));
+        text.push_str(&format!("{}\n", track.path().display()));
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
@@ -1,7 +1,13 @@
-use std::collections::{HashMap, HashSet, LinkedList};
+use std::collections::{HashSet, LinkedList};
+use std::fmt;
 
 use loggit::{debug, info, trace};
 
+use crate::profiles::{ProfileError, Profiles};
+use crate::rules::TransitionRules;
+use crate::scorer::TransitionScorer;
+use crate::search::{is_better, Node, Pair, ScoredList, SearchSpace};
+use crate::solver::{BeamSolver, KeylessPolicy, Solver, SortRequest, Transposition};
 use crate::types::key::Key;
 use crate::types::track::Track;
 
@@ -18,9 +24,71 @@ pub enum Movement {
     SubDomKey,
     ToneBoost,
     ToneDrop,
+    /// A user-defined move, holding the index of its rule in the
+    /// [`TransitionRules`] table it came from.
+    Custom(u16),
+    /// A move from or to a track without a key, placed as a wildcard, see
+    /// [`crate::solver::KeylessPolicy`].
+    Wildcard,
 }
 
-#[derive(Debug, Clone)]
+impl Movement {
+    pub const BUILT_IN: [Movement; 11] = [
+        Movement::PerfectMatch,
+        Movement::EnergyBoost,
+        Movement::EnergyDrop,
+        Movement::EnergySwitch,
+        Movement::MoodBoost,
+        Movement::MoodDrop,
+        Movement::EnergyRaise,
+        Movement::DomKey,
+        Movement::SubDomKey,
+        Movement::ToneBoost,
+        Movement::ToneDrop,
+    ];
+
+    /// Name of a built-in movement; custom movements are named by their rule.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Movement::PerfectMatch => "PerfectMatch",
+            Movement::EnergyBoost => "EnergyBoost",
+            Movement::EnergyDrop => "EnergyDrop",
+            Movement::EnergySwitch => "EnergySwitch",
+            Movement::MoodBoost => "MoodBoost",
+            Movement::MoodDrop => "MoodDrop",
+            Movement::EnergyRaise => "EnergyRaise",
+            Movement::DomKey => "DomKey",
+            Movement::SubDomKey => "SubDomKey",
+            Movement::ToneBoost => "ToneBoost",
+            Movement::ToneDrop => "ToneDrop",
+            Movement::Custom(_) => "Custom",
+            Movement::Wildcard => "Wildcard",
+        }
+    }
+
+    /// Parses a built-in movement name, ignoring case, `_` and `-`.
+    pub fn from_name(value: &str) -> Option<Self> {
+        let normalized: String = value
+            .chars()
+            .filter(|c| *c != '_' && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect();
+        Movement::BUILT_IN
+            .into_iter()
+            .find(|movement| movement.name().to_lowercase() == normalized)
+    }
+}
+
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Movement::Custom(index) => write!(f, "Custom#{index}"),
+            movement => write!(f, "{}", movement.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MovementWeights {
     pub perfect_match: i32,
     pub energy_boost: i32,
@@ -67,21 +135,107 @@ impl MovementWeights {
             Movement::SubDomKey => self.sub_dom_key,
             Movement::ToneBoost => self.tone_boost,
             Movement::ToneDrop => self.tone_drop,
+            Movement::Custom(_) | Movement::Wildcard => 0,
+        }
+    }
+
+    /// Sets the weight of a built-in movement; custom movements are weighted
+    /// by their rule and wildcards by the keyless policy, so both are left
+    /// alone.
+    pub fn set_weight(&mut self, movement: Movement, weight: i32) {
+        let field = match movement {
+            Movement::PerfectMatch => &mut self.perfect_match,
+            Movement::EnergyBoost => &mut self.energy_boost,
+            Movement::EnergyDrop => &mut self.energy_drop,
+            Movement::EnergySwitch => &mut self.energy_switch,
+            Movement::MoodBoost => &mut self.mood_boost,
+            Movement::MoodDrop => &mut self.mood_drop,
+            Movement::EnergyRaise => &mut self.energy_raise,
+            Movement::DomKey => &mut self.dom_key,
+            Movement::SubDomKey => &mut self.sub_dom_key,
+            Movement::ToneBoost => &mut self.tone_boost,
+            Movement::ToneDrop => &mut self.tone_drop,
+            Movement::Custom(_) | Movement::Wildcard => return,
+        };
+        *field = weight;
+    }
+}
+
+/// Scoring that looks at the last few moves of a list, to keep long chains
+/// of the same movement or key from winning on raw weight alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryWeights {
+    /// number of previous transitions taken into account
+    pub window: usize,
+    /// subtracted once for every move in the window equal to the new one
+    pub repeat_penalty: i32,
+    /// most tracks in the same key allowed back to back
+    pub max_same_key_run: Option<usize>,
+    /// added when the new move differs from every move in the window
+    pub variety_bonus: i32,
+}
+
+impl Default for HistoryWeights {
+    fn default() -> Self {
+        Self {
+            window: 3,
+            repeat_penalty: 5,
+            max_same_key_run: Some(3),
+            variety_bonus: 3,
+        }
+    }
+}
+
+impl HistoryWeights {
+    /// Score adjustment for playing `movement` after `recent` (newest last),
+    /// whose window is already cut to at most `self.window` moves.
+    pub fn adjustment(&self, recent: &[Movement], movement: Movement) -> i32 {
+        if recent.is_empty() {
+            return 0;
+        }
+        let repeats = recent.iter().filter(|&&past| past == movement).count() as i32;
+        if repeats == 0 {
+            self.variety_bonus
+        } else {
+            -self.repeat_penalty * repeats
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Pair {
-    start: usize,
-    end: usize,
-    weight: i32,
+/// Discounts transitions between tracks whose keys were detected with low
+/// confidence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfidenceWeights {
+    /// confidence assumed for tracks without one, e.g. keys read from tags
+    pub unknown: f32,
+    /// transitions whose combined confidence is lower are reported as risky
+    pub risky_below: f32,
 }
 
-#[derive(Debug, Clone)]
-struct ScoredList {
-    list: LinkedList<usize>,
-    score: i32,
+impl Default for ConfidenceWeights {
+    fn default() -> Self {
+        Self {
+            unknown: 1.0,
+            risky_below: 0.3,
+        }
+    }
+}
+
+impl ConfidenceWeights {
+    /// Combined confidence of a transition between `start` and `end`.
+    pub fn of(&self, start: &Track, end: &Track) -> f32 {
+        let confidence = |track: &Track| track.key_confidence().unwrap_or(self.unknown);
+        confidence(start) * confidence(end)
+    }
+
+    /// `weight` scaled by `confidence`; negative weights are left as they are
+    /// so an unsure key never makes a bad move look better.
+    pub fn discount(&self, weight: i32, confidence: f32) -> i32 {
+        if weight <= 0 {
+            return weight;
+        }
+        (weight as f32 * confidence.clamp(0.0, 1.0)).round() as i32
+    }
 }
 
 pub fn melodic_sort(tracks: &[Track], limit: usize) -> LinkedList<Track> {
@@ -93,32 +247,87 @@ pub fn melodic_sort_with_weights(
     weights: &MovementWeights,
     limit: usize,
 ) -> LinkedList<Track> {
-    info!("melodic_sort: tracks={}", tracks.len());
-    let pairs = build_pairs(tracks, weights);
-    info!("melodic_sort: pairs={}", pairs.len());
-    if pairs.is_empty() {
-        return LinkedList::new();
-    }
+    let mut request = SortRequest::new(tracks, weights);
+    request.budget.beam_width = limit;
+    BeamSolver
+        .solve(&request)
+        .tracks(tracks)
+        .into_iter()
+        .collect()
+}
 
-    let mut pairs_by_start: HashMap<usize, Vec<Pair>> = HashMap::new();
-    for pair in pairs {
-        pairs_by_start.entry(pair.start).or_default().push(pair);
-    }
+/// Like [`melodic_sort_with_weights`], but every transition is scored by
+/// `scorer`, see [`crate::scorer`].
+pub fn melodic_sort_with_scorer(
+    tracks: &[Track],
+    weights: &MovementWeights,
+    scorer: &dyn TransitionScorer,
+    limit: usize,
+) -> LinkedList<Track> {
+    let mut request = SortRequest::new(tracks, weights);
+    request.scorer = Some(scorer);
+    request.budget.beam_width = limit;
+    BeamSolver
+        .solve(&request)
+        .tracks(tracks)
+        .into_iter()
+        .collect()
+}
 
-    let mut current_layer: Vec<ScoredList> = pairs_by_start
-        .values()
-        .flat_map(|pairs| pairs.iter())
-        .map(|pair| {
-            let mut list = LinkedList::new();
-            list.push_back(pair.start);
-            list.push_back(pair.end);
-            ScoredList {
-                list,
-                score: pair.weight,
-            }
-        })
+/// [`melodic_sort_with_weights`] with one of the built-in profiles of
+/// [`crate::profiles::PROFILE_NAMES`], e.g. `"safe"`.
+pub fn melodic_sort_with_profile(
+    tracks: &[Track],
+    profile: &str,
+    limit: usize,
+) -> Result<LinkedList<Track>, ProfileError> {
+    let profiles = Profiles::builtin();
+    let weights = profiles.get(profile)?;
+    Ok(melodic_sort_with_weights(tracks, weights, limit))
+}
+
+/// Like [`melodic_sort_with_weights`], but tracks may be pitch-shifted as
+/// allowed by `transposition`; every track comes with its shift in semitones.
+pub fn melodic_sort_with_transposition(
+    tracks: &[Track],
+    weights: &MovementWeights,
+    transposition: Transposition,
+    limit: usize,
+) -> LinkedList<(Track, i8)> {
+    let mut request = SortRequest::new(tracks, weights);
+    request.transposition = Some(transposition);
+    request.budget.beam_width = limit;
+    let result = BeamSolver.solve(&request);
+    result
+        .tracks(tracks)
+        .into_iter()
+        .zip(result.shifts)
+        .collect()
+}
+
+pub(crate) fn beam_search(space: &SearchSpace, limit: usize) -> Option<ScoredList> {
+    info!("melodic_sort: tracks={}", space.request.tracks.len());
+    info!("melodic_sort: pairs={}", space.pair_count());
+
+    let opening: Vec<ScoredList> = space
+        .opening_pairs()
+        .into_iter()
+        .filter_map(|pair| space.open(&pair))
         .collect();
-    current_layer = trim_top_lists(current_layer, limit);
+    beam_search_from(space, opening, limit)
+}
+
+/// Beam search extending the lists of `opening`, which all have the same
+/// length.
+pub(crate) fn beam_search_from(
+    space: &SearchSpace,
+    opening: Vec<ScoredList>,
+    limit: usize,
+) -> Option<ScoredList> {
+    if opening.is_empty() {
+        return None;
+    }
+    let mut current_layer = trim_top_lists(opening, limit);
     let mut layer_idx = 0usize;
     info!(
         "melodic_sort: layer={} lists={}",
@@ -126,7 +335,10 @@ pub fn melodic_sort_with_weights(
         current_layer.len()
     );
 
-    let mut next_layer = extend_layer(layer_idx, &current_layer, &pairs_by_start, limit);
+    // every layer is a candidate, a cyclic set may only close at a shorter one
+    let mut best: Option<ScoredList> = None;
+    keep_best(&mut best, &current_layer, space);
+    let mut next_layer = extend_layer(layer_idx, &current_layer, space, limit);
     while !next_layer.is_empty() {
         info!(
             "melodic_sort: expanded layer {} lists={} -> {}",
@@ -136,46 +348,51 @@ pub fn melodic_sort_with_weights(
         );
         current_layer = next_layer;
         layer_idx += 1;
-        next_layer = extend_layer(layer_idx, &current_layer, &pairs_by_start, limit);
+        keep_best(&mut best, &current_layer, space);
+        next_layer = extend_layer(layer_idx, &current_layer, space, limit);
     }
     info!(
         "melodic_sort: finished at layer={}, total_lists={}",
         layer_idx,
         current_layer.len()
     );
+    if let Some(best) = &best {
+        info!(
+            "melodic_sort: best_list_len={}, best_score={}",
+            best.list.len(),
+            best.score
+        );
+    }
+
+    best
+}
 
-    let mut best_list: Option<&LinkedList<usize>> = None;
-    let mut best_len = 0usize;
-    let mut best_score = i32::MIN;
-    for scored in &current_layer {
+/// Replaces `best` with the best list of `layer` that can end the set,
+/// scored with its closing transition.
+fn keep_best(best: &mut Option<ScoredList>, layer: &[ScoredList], space: &SearchSpace) {
+    for scored in layer {
         let len = scored.list.len();
-        let score = scored.score;
+        let Some(closing) = space.close(&scored.list) else {
+            continue;
+        };
+        let score = scored.score + closing;
         trace!("melodic_sort: list_len={}, score={}", len, score);
-        if len > best_len || (len == best_len && score > best_score) {
-            best_len = len;
-            best_score = score;
-            best_list = Some(&scored.list);
+        let replace = best
+            .as_ref()
+            .is_none_or(|best| is_better(len, score, best.list.len(), best.score));
+        if replace {
+            *best = Some(ScoredList {
+                list: scored.list.clone(),
+                score,
+            });
         }
     }
-    info!(
-        "melodic_sort: best_list_len={}, best_score={}",
-        best_len, best_score
-    );
-
-    let mut result = LinkedList::new();
-    if let Some(best) = best_list {
-        for &index in best {
-            result.push_back(tracks[index].clone());
-        }
-    }
-
-    result
 }
 
 fn extend_layer(
     layer_idx: usize,
     layer: &[ScoredList],
-    pairs_by_start: &HashMap<usize, Vec<Pair>>,
+    space: &SearchSpace,
     limit: usize,
 ) -> Vec<ScoredList> {
     let mut next_layer: Vec<ScoredList> = Vec::new();
@@ -188,27 +405,25 @@ fn extend_layer(
     );
     for scored in layer {
         let list = &scored.list;
-        let Some(&end) = list.back() else { continue };
-        let Some(pairs) = pairs_by_start.get(&end) else { continue };
+        let Some(&end) = list.last() else { continue };
 
-        for pair in pairs {
-            if list_contains(list, pair.end) {
+        for pair in space.successors(end) {
+            let Some(gain) = space.step(list, pair) else {
                 trace!(
-                    "extend_layer: layer={}, skip duplicate list_end={} candidate_end={}",
+                    "extend_layer: layer={}, skip list_end={} candidate_end={}",
                     layer_idx,
                     end,
                     pair.end
                 );
                 continue;
-            }
+            };
 
             let mut new_list = list.clone();
-            new_list.push_back(pair.end);
-            let key: Vec<usize> = new_list.iter().copied().collect();
-            if seen.insert(key) {
+            new_list.push(pair.end);
+            if seen.insert(new_list.clone()) {
                 next_layer.push(ScoredList {
                     list: new_list,
-                    score: scored.score + pair.weight,
+                    score: scored.score + gain,
                 });
             }
         }
@@ -225,32 +440,46 @@ fn extend_layer(
     trimmed
 }
 
-fn list_contains(list: &LinkedList<usize>, target: usize) -> bool {
-    list.iter().any(|&value| value == target)
-}
-
 fn trim_top_lists(lists: Vec<ScoredList>, limit: usize) -> Vec<ScoredList> {
     if lists.len() <= limit {
         return lists;
     }
 
     let mut scored = lists;
-    scored.sort_by(|a, b| b.score.cmp(&a.score));
+    scored.sort_by_key(|list| std::cmp::Reverse(list.score));
     scored.truncate(limit);
     scored
 }
 
-fn build_pairs(tracks: &[Track], weights: &MovementWeights) -> Vec<Pair> {
+/// Every transition `rules` allow from the outro of one node into the intro of
+/// a node of another track, plus the wildcard ones of keyless tracks, leaving
+/// out the ones the request's constraints forbid.
+pub(crate) fn build_pairs(
+    nodes: &[Node],
+    rules: &TransitionRules,
+    request: &SortRequest,
+) -> Vec<Pair> {
+    let constraints = &request.constraints;
     let mut pairs = Vec::new();
 
-    for (i, start) in tracks.iter().enumerate() {
-        let Some(start_key) = start.key() else { continue };
-        for (j, end) in tracks.iter().enumerate() {
-            if i == j {
+    for (i, start) in nodes.iter().enumerate() {
+        for (j, end) in nodes.iter().enumerate() {
+            if start.track == end.track {
                 continue;
             }
-            let Some(end_key) = end.key() else { continue };
-            if let Some(movement) = movement_between(start_key, end_key) {
+            let found = match (start.outro, end.intro) {
+                (Some(outro), Some(intro)) => rules.find(&outro, &intro),
+                (None, None) => None,
+                _ => wildcard(request, start.track, end.track),
+            };
+            if let Some((movement, weight)) = found {
+                let (from, to) = (&request.tracks[start.track], &request.tracks[end.track]);
+                if constraints.forbids(start.track, end.track, movement)
+                    || constraints.too_far(from, to)
+                {
+                    trace!("build_pairs: {} -> {} forbidden", i, j);
+                    continue;
+                }
                 trace!(
                     "build_pairs: {} -> {} movement={:?}",
                     i,
@@ -260,7 +489,8 @@ fn build_pairs(tracks: &[Track], weights: &MovementWeights) -> Vec<Pair> {
                 pairs.push(Pair {
                     start: i,
                     end: j,
-                    weight: weights.weight(movement),
+                    movement,
+                    weight,
                 });
             }
         }
@@ -269,7 +499,36 @@ fn build_pairs(tracks: &[Track], weights: &MovementWeights) -> Vec<Pair> {
     pairs
 }
 
-fn movement_between(start: &Key, end: &Key) -> Option<Movement> {
+/// The wildcard move between two tracks, one of them without a key, if the
+/// keyless policy allows it.
+fn wildcard(request: &SortRequest, start: usize, end: usize) -> Option<(Movement, i32)> {
+    let KeylessPolicy::Wildcard {
+        weight,
+        max_bpm_diff,
+        max_energy_diff,
+    } = request.keyless
+    else {
+        return None;
+    };
+    if let Some(max_bpm_diff) = max_bpm_diff {
+        let start_bpm = request.tracks[start].bpm()?;
+        let end_bpm = request.tracks[end].bpm()?;
+        if (start_bpm - end_bpm).abs() > max_bpm_diff {
+            return None;
+        }
+    }
+    if let Some(max_energy_diff) = max_energy_diff {
+        let start_energy = request.tracks[start].energy()?;
+        let end_energy = request.tracks[end].energy()?;
+        if (start_energy - end_energy).abs() > max_energy_diff {
+            return None;
+        }
+    }
+    Some((Movement::Wildcard, weight))
+}
+
+/// The built-in Camelot wheel movement between two keys.
+pub fn movement_between(start: &Key, end: &Key) -> Option<Movement> {
     let delta = forward_delta(start.number(), end.number());
     let same_letter = start.letter() == end.letter();
 
@@ -295,7 +554,7 @@ fn movement_between(start: &Key, end: &Key) -> Option<Movement> {
     }
 }
 
-fn forward_delta(start: u8, end: u8) -> u8 {
+pub(crate) fn forward_delta(start: u8, end: u8) -> u8 {
     let start = (start - 1) as i16;
     let end = (end - 1) as i16;
     ((end - start + 12) % 12) as u8
@@ -318,4 +577,13 @@ mod tests {
         let end = Key::new(4, KeyLetter::B).unwrap();
         assert_eq!(movement_between(&start, &end), Some(Movement::MoodDrop));
     }
+
+    #[test]
+    fn history_rewards_variety_and_penalizes_repeats() {
+        let history = HistoryWeights::default();
+        let recent = [Movement::PerfectMatch, Movement::PerfectMatch];
+        assert_eq!(history.adjustment(&recent, Movement::PerfectMatch), -10);
+        assert_eq!(history.adjustment(&recent, Movement::EnergyBoost), 3);
+        assert_eq!(history.adjustment(&[], Movement::EnergyBoost), 0);
+    }
 }
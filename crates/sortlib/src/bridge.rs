@@ -0,0 +1,175 @@
+use loggit::debug;
+
+use crate::search::{ScoredList, SearchSpace};
+use crate::solver::{SortRequest, SortResult};
+
+/// What makes one bridge better than another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BridgeGoal {
+    /// fewest intermediate tracks, the best score among those
+    #[default]
+    Shortest,
+    /// best total score, however many intermediate tracks it takes
+    BestScore,
+}
+
+#[derive(Debug, Clone)]
+pub struct BridgeOptions {
+    pub goal: BridgeGoal,
+    /// most tracks played between the two ends
+    pub max_intermediates: usize,
+    /// tracks never used as a bridge
+    pub excluded: Vec<usize>,
+}
+
+impl Default for BridgeOptions {
+    fn default() -> Self {
+        Self {
+            goal: BridgeGoal::default(),
+            max_intermediates: 3,
+            excluded: Vec::new(),
+        }
+    }
+}
+
+/// A chain of tracks of `request.tracks` leading from `from` to `to`, both
+/// included, or `None` when there is none within `options`.
+///
+/// Every step follows the request's rules, scoring and constraints, except
+/// that the chain is never cyclic. Each track of the library plays at most
+/// once. Every chain is followed, since the tracks and artists already played
+/// decide what may come next; `options.max_intermediates` keeps them few.
+pub fn find_bridge(
+    request: &SortRequest,
+    from: usize,
+    to: usize,
+    options: &BridgeOptions,
+) -> Option<SortResult> {
+    if from == to {
+        return None;
+    }
+    let mut request = request.clone();
+    request.constraints.cyclic = false;
+    let space = SearchSpace::new(&request);
+
+    let mut layer: Vec<ScoredList> = space
+        .track_nodes(from)
+        .into_iter()
+        .map(|node| space.start(node))
+        .collect();
+    let mut best: Option<ScoredList> = None;
+    for intermediates in 0..=options.max_intermediates {
+        let mut next: Vec<ScoredList> = Vec::new();
+        for scored in &layer {
+            let last = scored.list[scored.list.len() - 1];
+            for pair in space.successors(last) {
+                let track = space.node(pair.end).track;
+                if track != to
+                    && (intermediates == options.max_intermediates
+                        || options.excluded.contains(&track))
+                {
+                    continue;
+                }
+                let Some(gain) = space.step(&scored.list, pair) else {
+                    continue;
+                };
+                let mut list = scored.list.clone();
+                list.push(pair.end);
+                let extended = ScoredList {
+                    list,
+                    score: scored.score + gain,
+                };
+                if track == to {
+                    if best.as_ref().is_none_or(|best| extended.score > best.score) {
+                        best = Some(extended);
+                    }
+                    continue;
+                }
+                next.push(extended);
+            }
+        }
+        if best.is_some() && options.goal == BridgeGoal::Shortest {
+            break;
+        }
+        debug!(
+            "find_bridge: {} intermediates, {} open chains",
+            intermediates + 1,
+            next.len()
+        );
+        layer = next;
+    }
+    best.map(|best| space.ordered_result("bridge", best))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::MovementWeights;
+    use crate::types::track::Track;
+
+    #[test]
+    fn bridges_keys_that_share_no_movement() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "3B"),
+            Track::from_pair("c", "8B"),
+            Track::from_pair("d", "9A"),
+            Track::from_pair("e", "9B"),
+            Track::from_pair("f", "4B"),
+        ];
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+
+        let shortest = find_bridge(&request, 0, 1, &BridgeOptions::default()).unwrap();
+        assert_eq!(shortest.order, vec![0, 2, 1]);
+        assert_eq!(shortest.score, weights.energy_switch + weights.energy_raise);
+
+        let options = BridgeOptions {
+            goal: BridgeGoal::BestScore,
+            ..BridgeOptions::default()
+        };
+        let best = find_bridge(&request, 0, 1, &options).unwrap();
+        assert_eq!(best.len(), 5);
+        assert_eq!(best.score, 35);
+
+        let options = BridgeOptions {
+            max_intermediates: 1,
+            excluded: vec![2],
+            ..BridgeOptions::default()
+        };
+        assert!(find_bridge(&request, 0, 1, &options).is_none());
+        let options = BridgeOptions {
+            max_intermediates: 2,
+            ..options
+        };
+        let detour = find_bridge(&request, 0, 1, &options).unwrap();
+        assert_eq!(detour.order, vec![0, 4, 5, 1]);
+    }
+
+    #[test]
+    fn follows_chains_a_better_one_into_the_same_track_blocks() {
+        // a -> y -> x beats a -> z -> x, but only y leads on to t, which
+        // shares an artist with a
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_artists(["Burr Oak"]),
+            Track::from_pair("y", "8A"),
+            Track::from_pair("z", "8A"),
+            Track::from_pair("x", "8A"),
+            Track::from_pair("t", "8A").with_artists(["Burr Oak"]),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.artist_gap = 3;
+        let allowed = [(0, 1), (0, 2), (1, 3), (2, 3), (3, 1), (1, 4)];
+        for from in 0..tracks.len() {
+            for to in 0..tracks.len() {
+                if from != to && !allowed.contains(&(from, to)) {
+                    request.constraints.forbidden_pairs.push((from, to));
+                }
+            }
+        }
+
+        let bridge = find_bridge(&request, 0, 4, &BridgeOptions::default()).unwrap();
+        assert_eq!(bridge.order, vec![0, 2, 3, 1, 4]);
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use loggit::debug;
+
+use crate::algorithm::MovementWeights;
+use crate::matrix::CompatibilityMatrix;
+use crate::types::key::Key;
+use crate::types::track::Track;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CrateOptions {
+    /// tracks further apart in tempo are not linked, and closer ones are
+    /// linked more weakly the further apart they are; tempo is ignored when
+    /// `None` or when a track's BPM is unknown
+    pub max_bpm_diff: Option<f32>,
+    /// groups with fewer tracks are not suggested
+    pub min_size: usize,
+    /// above 1 the crates get smaller, below 1 larger
+    pub resolution: f32,
+}
+
+impl Default for CrateOptions {
+    fn default() -> Self {
+        Self {
+            max_bpm_diff: Some(10.0),
+            min_size: 3,
+            resolution: 1.0,
+        }
+    }
+}
+
+/// A group of tracks that mix well with each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestedCrate {
+    /// indices into the library, in library order
+    pub tracks: Vec<usize>,
+    /// the most common keys, most common first
+    pub keys: Vec<Key>,
+    /// slowest and fastest known tempo
+    pub bpm: Option<(f32, f32)>,
+}
+
+impl SuggestedCrate {
+    /// A short description, e.g. `8A 9A 4B | 170-175 BPM`.
+    pub fn label(&self) -> String {
+        let keys: Vec<String> = self.keys.iter().map(Key::to_string).collect();
+        match self.bpm {
+            Some((min, max)) if min.round() == max.round() => {
+                format!("{} | {:.0} BPM", keys.join(" "), min)
+            }
+            Some((min, max)) => format!("{} | {:.0}-{:.0} BPM", keys.join(" "), min, max),
+            None => keys.join(" "),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// The crate's tracks, cloned out of `library`.
+    pub fn of(&self, library: &[Track]) -> Vec<Track> {
+        self.tracks
+            .iter()
+            .map(|&index| library[index].clone())
+            .collect()
+    }
+}
+
+/// Splits `library` into crates of tracks that mix into each other, largest
+/// first, by modularity-based community detection (Louvain) over the
+/// compatibility graph. Two tracks are linked by the better of their two
+/// transitions under `weights`, scaled down by their tempo difference.
+/// Keyless tracks are left out.
+pub fn suggest_crates(
+    library: &[Track],
+    weights: &MovementWeights,
+    options: &CrateOptions,
+) -> Vec<SuggestedCrate> {
+    let matrix = CompatibilityMatrix::new(library, weights);
+    let mut adjacency: Vec<Vec<(usize, f32)>> = vec![Vec::new(); library.len()];
+    for (i, neighbours) in adjacency.iter_mut().enumerate() {
+        for j in (0..library.len()).filter(|&j| j != i) {
+            let best = matrix.weight(i, j).max(matrix.weight(j, i));
+            let Some(best) = best else { continue };
+            let link = (best.max(0) + 1) as f32 * tempo_factor(&library[i], &library[j], options);
+            if link > 0.0 {
+                neighbours.push((j, link));
+            }
+        }
+    }
+
+    let membership = louvain(adjacency, options.resolution);
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (track, &community) in membership.iter().enumerate() {
+        if library[track].key().is_some() {
+            groups.entry(community).or_default().push(track);
+        }
+    }
+    let mut crates: Vec<SuggestedCrate> = groups
+        .into_values()
+        .filter(|tracks| tracks.len() >= options.min_size)
+        .map(|tracks| describe(library, tracks))
+        .collect();
+    crates.sort_by(|a, b| b.len().cmp(&a.len()).then(a.tracks.cmp(&b.tracks)));
+    debug!(
+        "suggest_crates: {} tracks -> {} crates",
+        library.len(),
+        crates.len()
+    );
+    crates
+}
+
+/// 1 for tracks at the same tempo down to 0 at `max_bpm_diff` apart.
+fn tempo_factor(a: &Track, b: &Track, options: &CrateOptions) -> f32 {
+    match (options.max_bpm_diff, a.bpm(), b.bpm()) {
+        (Some(max_bpm_diff), Some(a), Some(b)) if max_bpm_diff > 0.0 => {
+            (1.0 - (a - b).abs() / max_bpm_diff).max(0.0)
+        }
+        _ => 1.0,
+    }
+}
+
+fn describe(library: &[Track], tracks: Vec<usize>) -> SuggestedCrate {
+    let mut counts: Vec<(Key, usize)> = Vec::new();
+    for key in tracks.iter().filter_map(|&track| library[track].key()) {
+        match counts.iter_mut().find(|(other, _)| other == key) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*key, 1)),
+        }
+    }
+    // stable, so equally common keys stay in library order
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    let keys = counts.into_iter().take(3).map(|(key, _)| key).collect();
+
+    let bpm = tracks
+        .iter()
+        .filter_map(|&track| library[track].bpm())
+        .fold(None, |range: Option<(f32, f32)>, bpm| match range {
+            Some((min, max)) => Some((min.min(bpm), max.max(bpm))),
+            None => Some((bpm, bpm)),
+        });
+    SuggestedCrate { tracks, keys, bpm }
+}
+
+/// Community of every node of an undirected weighted graph, given as the
+/// symmetric neighbour lists of its nodes.
+fn louvain(adjacency: Vec<Vec<(usize, f32)>>, resolution: f32) -> Vec<usize> {
+    let mut membership: Vec<usize> = (0..adjacency.len()).collect();
+    let mut degrees: Vec<f32> = adjacency
+        .iter()
+        .map(|neighbours| neighbours.iter().map(|&(_, weight)| weight).sum())
+        .collect();
+    let mut graph = adjacency;
+    loop {
+        let communities = local_moving(&graph, &degrees, resolution);
+
+        // number the communities densely and merge each into a node
+        let mut numbers: HashMap<usize, usize> = HashMap::new();
+        for &community in &communities {
+            let next = numbers.len();
+            numbers.entry(community).or_insert(next);
+        }
+        if numbers.len() == graph.len() {
+            return membership;
+        }
+        let community_of: Vec<usize> = communities.iter().map(|c| numbers[c]).collect();
+        for community in &mut membership {
+            *community = community_of[*community];
+        }
+
+        let mut merged: Vec<HashMap<usize, f32>> = vec![HashMap::new(); numbers.len()];
+        let mut merged_degrees = vec![0.0; numbers.len()];
+        for (node, neighbours) in graph.iter().enumerate() {
+            let from = community_of[node];
+            merged_degrees[from] += degrees[node];
+            for &(neighbour, weight) in neighbours {
+                let to = community_of[neighbour];
+                if from != to {
+                    *merged[from].entry(to).or_default() += weight;
+                }
+            }
+        }
+        graph = merged
+            .into_iter()
+            .map(|neighbours| {
+                let mut neighbours: Vec<(usize, f32)> = neighbours.into_iter().collect();
+                neighbours.sort_by_key(|&(neighbour, _)| neighbour);
+                neighbours
+            })
+            .collect();
+        degrees = merged_degrees;
+    }
+}
+
+/// Moves every node into the neighbouring community that raises the
+/// modularity most, until no move does. Nodes are visited in order, so the
+/// result is deterministic.
+fn local_moving(graph: &[Vec<(usize, f32)>], degrees: &[f32], resolution: f32) -> Vec<usize> {
+    let total: f32 = degrees.iter().sum();
+    let mut communities: Vec<usize> = (0..graph.len()).collect();
+    if total == 0.0 {
+        return communities;
+    }
+    // summed degree of every community
+    let mut community_degrees = degrees.to_vec();
+    let mut moved = true;
+    while moved {
+        moved = false;
+        for node in 0..graph.len() {
+            let current = communities[node];
+            community_degrees[current] -= degrees[node];
+
+            let mut links: Vec<(usize, f32)> = Vec::new();
+            for &(neighbour, weight) in &graph[node] {
+                let community = communities[neighbour];
+                match links.iter_mut().find(|(other, _)| *other == community) {
+                    Some((_, sum)) => *sum += weight,
+                    None => links.push((community, weight)),
+                }
+            }
+            let gain = |community: usize, link: f32| {
+                link - resolution * community_degrees[community] * degrees[node] / total
+            };
+            let stay = links
+                .iter()
+                .find(|&&(community, _)| community == current)
+                .map_or(0.0, |&(_, link)| link);
+            let (mut best, mut best_gain) = (current, gain(current, stay));
+            for &(community, link) in &links {
+                let gain = gain(community, link);
+                if gain > best_gain + 1e-6 {
+                    (best, best_gain) = (community, gain);
+                }
+            }
+
+            community_degrees[best] += degrees[node];
+            if best != current {
+                communities[node] = best;
+                moved = true;
+            }
+        }
+    }
+    communities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_library_by_key_and_tempo() {
+        let library = vec![
+            Track::from_pair("a", "8A").with_bpm(174.0),
+            Track::from_pair("b", "3B").with_bpm(128.0),
+            Track::from_pair("c", "9A").with_bpm(172.0),
+            Track::from_pair("d", "4B").with_bpm(126.0),
+            Track::from_pair("e", "8A").with_bpm(175.0),
+            Track::from_pair("f", "3B").with_bpm(128.0),
+            Track::new(None, "g", "", None),
+            // mixes with a, c and e, but is far too slow
+            Track::from_pair("h", "8A").with_bpm(90.0),
+        ];
+        let weights = MovementWeights::default();
+        let crates = suggest_crates(&library, &weights, &CrateOptions::default());
+
+        let groups: Vec<&[usize]> = crates.iter().map(|c| c.tracks.as_slice()).collect();
+        assert_eq!(groups, vec![&[0, 2, 4][..], &[1, 3, 5][..]]);
+        assert_eq!(crates[0].label(), "8A 9A | 172-175 BPM");
+        assert_eq!(crates[1].label(), "3B 4B | 126-128 BPM");
+        assert_eq!(crates[1].of(&library)[0].name(), "b");
+    }
+}
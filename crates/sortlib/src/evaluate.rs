@@ -0,0 +1,302 @@
+use std::fmt;
+
+use crate::algorithm::{Movement, MovementWeights};
+use crate::rules::TransitionRules;
+use crate::search::SearchSpace;
+use crate::solver::SortRequest;
+use crate::types::key::Key;
+use crate::types::track::Track;
+
+/// Why a transition of a checked order does not work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clash {
+    /// one of the two tracks has no key
+    MissingKey,
+    /// no transition rule connects the two keys
+    KeysDontMix,
+    /// the keys mix, but the step breaks one of the request's constraints
+    Constraint,
+    /// the movement or the pair of tracks is forbidden by the constraints
+    Forbidden,
+}
+
+impl fmt::Display for Clash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Clash::MissingKey => write!(f, "missing key"),
+            Clash::KeysDontMix => write!(f, "keys don't mix"),
+            Clash::Constraint => write!(f, "breaks a constraint"),
+            Clash::Forbidden => write!(f, "forbidden"),
+        }
+    }
+}
+
+/// One transition of a checked order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckedTransition {
+    /// position of the outgoing track in the order
+    pub position: usize,
+    /// key the outgoing track is mixed out of, after tempo shifts and
+    /// including a separate outro key
+    pub from_key: Option<Key>,
+    /// key the incoming track is mixed into
+    pub to_key: Option<Key>,
+    /// the movement, `None` when the keys don't mix
+    pub movement: Option<Movement>,
+    /// score the step adds, 0 for clashes
+    pub weight: i32,
+    pub clash: Option<Clash>,
+    /// the key confidences of both tracks are too low to rely on the move
+    pub risky: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    /// one entry per pair of consecutive tracks, plus the closing one for a
+    /// cyclic request
+    pub transitions: Vec<CheckedTransition>,
+    /// sum of the transition weights
+    pub score: i32,
+    /// `score` per transition, to compare sets of different lengths
+    pub normalized: f32,
+}
+
+impl Evaluation {
+    pub fn clashes(&self) -> impl Iterator<Item = &CheckedTransition> {
+        self.transitions
+            .iter()
+            .filter(|transition| transition.clash.is_some())
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.clashes().next().is_none()
+    }
+
+    /// Describes every transition by the keys actually mixed, e.g.
+    /// `1: 8A -> 9A EnergyBoost (+10)` or `2: 9A -> 3B clash: keys don't mix`.
+    pub fn explain(&self, rules: Option<&TransitionRules>) -> Vec<String> {
+        let key = |key: Option<Key>| key.map_or("?".to_string(), |key| key.to_string());
+        self.transitions
+            .iter()
+            .map(|transition| {
+                let position = transition.position;
+                let keys = format!("{} -> {}", key(transition.from_key), key(transition.to_key));
+                let name = transition.movement.map(|movement| match rules {
+                    Some(rules) => rules.name_of(movement).to_string(),
+                    None => movement.to_string(),
+                });
+                match (transition.clash, name) {
+                    (None, Some(name)) => {
+                        let risky = if transition.risky { " risky" } else { "" };
+                        format!(
+                            "{}: {keys} {name} ({:+}){risky}",
+                            position + 1,
+                            transition.weight
+                        )
+                    }
+                    (Some(clash), Some(name)) => {
+                        format!("{}: {keys} {name} clash: {clash}", position + 1)
+                    }
+                    (clash, None) => format!(
+                        "{}: {keys} clash: {}",
+                        position + 1,
+                        clash.unwrap_or(Clash::KeysDontMix)
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Checks `tracks` in the given order under `weights` and the Camelot rules.
+pub fn evaluate_order(tracks: &[Track], weights: &MovementWeights) -> Evaluation {
+    evaluate(&SortRequest::new(tracks, weights))
+}
+
+/// Checks `request.tracks` in the order given, with the request's rules,
+/// scoring and constraints, including the closing transition of a cyclic set.
+/// Tracks are taken unshifted, at `target_bpm` when set.
+pub fn evaluate(request: &SortRequest) -> Evaluation {
+    let space = SearchSpace::new(request);
+    let camelot;
+    let rules = match request.rules {
+        Some(rules) => rules,
+        None => {
+            camelot = TransitionRules::camelot(request.weights);
+            &camelot
+        }
+    };
+    let list: Vec<usize> = (0..request.tracks.len())
+        .filter_map(|track| space.track_node(track))
+        .collect();
+
+    let mut transitions: Vec<CheckedTransition> = (1..list.len())
+        .map(|position| {
+            let (start, end) = (list[position - 1], list[position]);
+            let (movement, weight, clash) = match space.pair_between(start, end) {
+                Some(pair) => match space.step(&list[..position], pair) {
+                    Some(gain) => (Some(pair.movement), gain, None),
+                    None => (Some(pair.movement), 0, Some(Clash::Constraint)),
+                },
+                None => unpaired(&space, rules, start, end),
+            };
+            CheckedTransition {
+                position: position - 1,
+                from_key: space.node(start).outro,
+                to_key: space.node(end).intro,
+                movement,
+                weight,
+                clash,
+                risky: space.is_risky(position - 1, position),
+            }
+        })
+        .collect();
+    if request.constraints.cyclic && list.len() > 1 {
+        let (first, last) = (list[0], list[list.len() - 1]);
+        let (movement, weight, clash) = match space.pair_between(last, first) {
+            Some(pair) => match space.close(&list) {
+                Some(gain) => (Some(pair.movement), gain, None),
+                None => (Some(pair.movement), 0, Some(Clash::Constraint)),
+            },
+            None => unpaired(&space, rules, last, first),
+        };
+        transitions.push(CheckedTransition {
+            position: list.len() - 1,
+            from_key: space.node(last).outro,
+            to_key: space.node(first).intro,
+            movement,
+            weight,
+            clash,
+            risky: space.is_risky(list.len() - 1, 0),
+        });
+    }
+
+    let score = transitions.iter().map(|transition| transition.weight).sum();
+    let normalized = match transitions.len() {
+        0 => 0.0,
+        len => score as f32 / len as f32,
+    };
+    Evaluation {
+        transitions,
+        score,
+        normalized,
+    }
+}
+
+/// Why the search graph has no pair between two nodes.
+fn unpaired(
+    space: &SearchSpace,
+    rules: &TransitionRules,
+    start: usize,
+    end: usize,
+) -> (Option<Movement>, i32, Option<Clash>) {
+    let (Some(outro), Some(intro)) = (space.node(start).outro, space.node(end).intro) else {
+        return (None, 0, Some(Clash::MissingKey));
+    };
+    match rules.find(&outro, &intro) {
+        // the keys mix, so the constraints rule the transition out
+        Some((movement, _)) => (Some(movement), 0, Some(Clash::Forbidden)),
+        None => (None, 0, Some(Clash::KeysDontMix)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_clashing_transitions() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "9A"),
+            Track::from_pair("c", "3B"),
+            Track::from_pair("d", "3A"),
+            Track::new(None, "e", "", None),
+        ];
+        let weights = MovementWeights::default();
+        let evaluation = evaluate_order(&tracks, &weights);
+
+        let movements: Vec<Option<Movement>> = evaluation
+            .transitions
+            .iter()
+            .map(|transition| transition.movement)
+            .collect();
+        assert_eq!(
+            movements,
+            vec![
+                Some(Movement::EnergyBoost),
+                None,
+                Some(Movement::EnergySwitch),
+                None
+            ]
+        );
+        let clashes: Vec<(usize, Clash)> = evaluation
+            .clashes()
+            .map(|transition| (transition.position, transition.clash.unwrap()))
+            .collect();
+        assert_eq!(
+            clashes,
+            vec![(1, Clash::KeysDontMix), (3, Clash::MissingKey)]
+        );
+        assert_eq!(evaluation.score, 20);
+        assert_eq!(evaluation.normalized, 5.0);
+        assert_eq!(
+            evaluation.explain(None)[1],
+            "2: 9A -> 3B clash: keys don't mix"
+        );
+    }
+
+    #[test]
+    fn constraint_breaks_are_clashes() {
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_artists(["Burr Oak"]),
+            Track::from_pair("b", "8A").with_artists(["Burr Oak"]),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.artist_gap = 1;
+
+        let evaluation = evaluate(&request);
+        assert!(!evaluation.is_valid());
+        assert_eq!(evaluation.transitions[0].clash, Some(Clash::Constraint));
+        assert_eq!(evaluation.transitions[0].weight, 0);
+        assert_eq!(evaluation.score, 0);
+        assert_eq!(
+            evaluation.transitions[0].movement,
+            Some(Movement::PerfectMatch)
+        );
+
+        request.constraints.artist_gap = 0;
+        request.constraints.forbidden_movements = vec![Movement::PerfectMatch];
+        let evaluation = evaluate(&request);
+        assert_eq!(evaluation.transitions[0].clash, Some(Clash::Forbidden));
+        assert_eq!(
+            evaluation.transitions[0].movement,
+            Some(Movement::PerfectMatch)
+        );
+    }
+
+    #[test]
+    fn explains_the_keys_actually_mixed() {
+        let outro = Key::from_camelot("9A").unwrap();
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_outro_key(outro),
+            Track::from_pair("b", "10A").with_bpm(164.0),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        let evaluation = evaluate(&request);
+        assert_eq!(
+            evaluation.explain(None),
+            vec!["1: 9A -> 10A EnergyBoost (+10)"]
+        );
+
+        // sped up to 174 BPM, 10A sounds a semitone up
+        request.target_bpm = Some(174.0);
+        let evaluation = evaluate(&request);
+        assert_eq!(
+            evaluation.explain(None),
+            vec!["1: 9A -> 5A clash: keys don't mix"]
+        );
+    }
+}
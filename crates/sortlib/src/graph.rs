@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde_json::json;
+
+use crate::algorithm::Movement;
+use crate::search::SearchSpace;
+use crate::solver::SortRequest;
+use crate::types::key::{Key, KeyLetter};
+
+/// A node of a [`CompatibilityGraph`]: a single track, or every track in one
+/// key once the graph is collapsed with [`CompatibilityGraph::by_key`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphNode {
+    pub label: String,
+    /// key the tracks are played in, `None` for a keyless track
+    pub key: Option<Key>,
+    /// tracks the node stands for, by index
+    pub tracks: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub movement: Movement,
+    /// score of the transition; the best one among the merged transitions
+    /// of a collapsed graph
+    pub weight: i32,
+    /// track transitions the edge stands for
+    pub count: usize,
+}
+
+/// Which tracks of a library mix into which, as a directed graph.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompatibilityGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl CompatibilityGraph {
+    /// One node per track of `request`, in the same order, with an edge for
+    /// every transition the request allows between unshifted tracks.
+    pub fn new(request: &SortRequest) -> Self {
+        let space = SearchSpace::new(request);
+        let mut graph = Self::default();
+        for (index, track) in request.tracks.iter().enumerate() {
+            let key = space
+                .track_node(index)
+                .and_then(|node| space.node(node).key);
+            graph.nodes.push(GraphNode {
+                label: track.name().to_string(),
+                key,
+                tracks: vec![index],
+            });
+            let Some(node) = space.track_node(index) else {
+                continue;
+            };
+            for pair in space.successors(node) {
+                let end = space.node(pair.end);
+                if end.shift != 0 {
+                    continue;
+                }
+                graph.edges.push(GraphEdge {
+                    from: index,
+                    to: end.track,
+                    movement: pair.movement,
+                    weight: pair.weight,
+                    count: 1,
+                });
+            }
+        }
+        graph
+    }
+
+    /// Collapses the nodes into the 24 Camelot keys, `1A` first and `12B`
+    /// last, merging the edges between tracks of the same two keys. Keyless
+    /// tracks and their edges are left out.
+    pub fn by_key(&self) -> Self {
+        let keys: Vec<Key> = (1..=12)
+            .flat_map(|number| {
+                [KeyLetter::A, KeyLetter::B].map(|letter| Key::new(number, letter).unwrap())
+            })
+            .collect();
+        let index_of = |key: Key| keys.iter().position(|&other| other == key);
+        let mut nodes: Vec<GraphNode> = keys
+            .iter()
+            .map(|&key| GraphNode {
+                label: key.to_string(),
+                key: Some(key),
+                tracks: Vec::new(),
+            })
+            .collect();
+        let mut node_of = vec![None; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let Some(key_index) = node.key.and_then(index_of) else {
+                continue;
+            };
+            nodes[key_index].tracks.extend(&node.tracks);
+            node_of[index] = Some(key_index);
+        }
+
+        let mut merged: BTreeMap<(usize, usize), GraphEdge> = BTreeMap::new();
+        for edge in &self.edges {
+            let (Some(from), Some(to)) = (node_of[edge.from], node_of[edge.to]) else {
+                continue;
+            };
+            merged
+                .entry((from, to))
+                .and_modify(|merged| {
+                    merged.weight = merged.weight.max(edge.weight);
+                    merged.count += edge.count;
+                })
+                .or_insert(GraphEdge {
+                    from,
+                    to,
+                    ..edge.clone()
+                });
+        }
+        Self {
+            nodes,
+            edges: merged.into_values().collect(),
+        }
+    }
+
+    /// The graph in Graphviz DOT, e.g. for `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph compatibility {\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let label = match (node.key, node.tracks.len()) {
+                (Some(key), 1) if node.label != key.to_string() => {
+                    format!("{} ({key})", node.label)
+                }
+                (_, 1) => node.label.clone(),
+                (_, count) => format!("{} ({count})", node.label),
+            };
+            let _ = writeln!(dot, "  {index} [label=\"{}\"];", escape_dot(&label));
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                dot,
+                "  {} -> {} [label=\"{} ({:+})\"];",
+                edge.from, edge.to, edge.movement, edge.weight
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph in GraphML, e.g. for Gephi or yEd.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <key id=\"key\" for=\"node\" attr.name=\"key\" attr.type=\"string\"/>\n",
+            "  <key id=\"tracks\" for=\"node\" attr.name=\"tracks\" attr.type=\"int\"/>\n",
+            "  <key id=\"movement\" for=\"edge\" attr.name=\"movement\" attr.type=\"string\"/>\n",
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+            "  <key id=\"count\" for=\"edge\" attr.name=\"count\" attr.type=\"int\"/>\n",
+            "  <graph id=\"compatibility\" edgedefault=\"directed\">\n",
+        ));
+        for (index, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(xml, "    <node id=\"n{index}\">");
+            let _ = writeln!(
+                xml,
+                "      <data key=\"label\">{}</data>",
+                escape_xml(&node.label)
+            );
+            if let Some(key) = node.key {
+                let _ = writeln!(xml, "      <data key=\"key\">{key}</data>");
+            }
+            let _ = writeln!(
+                xml,
+                "      <data key=\"tracks\">{}</data>",
+                node.tracks.len()
+            );
+            xml.push_str("    </node>\n");
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                xml,
+                "    <edge source=\"n{}\" target=\"n{}\">",
+                edge.from, edge.to
+            );
+            let _ = writeln!(xml, "      <data key=\"movement\">{}</data>", edge.movement);
+            let _ = writeln!(xml, "      <data key=\"weight\">{}</data>", edge.weight);
+            let _ = writeln!(xml, "      <data key=\"count\">{}</data>", edge.count);
+            xml.push_str("    </edge>\n");
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// The graph as JSON: `{"nodes": [{"id", "label", "key", "tracks"}],
+    /// "edges": [{"from", "to", "movement", "weight", "count"}]}`.
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                json!({
+                    "id": index,
+                    "label": node.label,
+                    "key": node.key.map(|key| key.to_string()),
+                    "tracks": node.tracks,
+                })
+            })
+            .collect();
+        let edges: Vec<_> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                json!({
+                    "from": edge.from,
+                    "to": edge.to,
+                    "movement": edge.movement.to_string(),
+                    "weight": edge.weight,
+                    "count": edge.count,
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&json!({ "nodes": nodes, "edges": edges }))
+            .expect("the graph is plain JSON")
+    }
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::MovementWeights;
+    use crate::types::track::Track;
+
+    fn graph() -> CompatibilityGraph {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "9A"),
+            Track::from_pair("c", "8A"),
+            Track::from_pair("d", "3B"),
+        ];
+        let weights = MovementWeights::default();
+        CompatibilityGraph::new(&SortRequest::new(&tracks, &weights))
+    }
+
+    #[test]
+    fn edges_carry_the_movement_and_weight() {
+        let graph = graph();
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.nodes[1].key, Key::from_camelot("9A").ok());
+        let from_a: Vec<(usize, Movement)> = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.from == 0)
+            .map(|edge| (edge.to, edge.movement))
+            .collect();
+        assert_eq!(
+            from_a,
+            vec![(1, Movement::EnergyBoost), (2, Movement::PerfectMatch)]
+        );
+        assert!(graph
+            .edges
+            .iter()
+            .all(|edge| edge.from != 3 && edge.to != 3));
+
+        let by_key = graph.by_key();
+        assert_eq!(by_key.nodes.len(), 24);
+        // 8A is the 15th key
+        assert_eq!(by_key.nodes[14].tracks, vec![0, 2]);
+        let boost = by_key
+            .edges
+            .iter()
+            .find(|edge| edge.movement == Movement::EnergyBoost)
+            .unwrap();
+        assert_eq!((boost.from, boost.to, boost.count), (14, 16, 2));
+    }
+
+    #[test]
+    fn exports_dot_graphml_and_json() {
+        let graph = graph();
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph compatibility {"));
+        assert!(dot.contains("  0 [label=\"a (8A)\"];"));
+        assert!(dot.contains("  0 -> 1 [label=\"EnergyBoost (+10)\"];"));
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<edge source=\"n0\" target=\"n1\">"));
+        assert_eq!(graphml.matches("<node ").count(), 4);
+
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json["nodes"][3]["key"], "3B");
+        assert_eq!(json["edges"][0]["movement"], "EnergyBoost");
+        assert_eq!(json["edges"].as_array().unwrap().len(), graph.edges.len());
+    }
+}
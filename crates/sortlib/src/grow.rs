@@ -0,0 +1,99 @@
+use loggit::info;
+
+use crate::search::SearchSpace;
+use crate::solver::{BeamSolver, KeylessHandling, KeylessPolicy, Solver, SortRequest, SortResult};
+
+/// A set of up to `len` tracks of the library `request.tracks` that opens
+/// with `seed`, chosen and ordered by the beam search.
+///
+/// The request's scoring and constraints apply as for any sort, including
+/// tempo, energy and artist limits; `Constraints::first` is replaced by
+/// `seed` and `Constraints::max_len` is capped at `len`. Keyless tracks only
+/// join the set as wildcards, never appended. A seed nothing follows makes a
+/// set of its own.
+pub fn grow_set(request: &SortRequest, seed: usize, len: usize) -> SortResult {
+    let mut request = request.clone();
+    request.constraints.first = Some(seed);
+    request.constraints.max_len = Some(request.constraints.max_len.map_or(len, |max| max.min(len)));
+    if request.keyless == KeylessPolicy::Append {
+        request.keyless = KeylessPolicy::Exclude;
+    }
+
+    let mut result = BeamSolver.solve(&request);
+    if result.order.is_empty() {
+        // nothing mixes out of the seed, so the set is the seed alone
+        let space = SearchSpace::new(&request);
+        if let Some(node) = space.track_node(seed) {
+            result = space.to_result("grow", Some(space.start(node)));
+        }
+    }
+    result.solver = "grow".to_string();
+    // the rest of the library was never meant to be in the set
+    result
+        .keyless
+        .retain(|keyless| keyless.handling == KeylessHandling::Wildcard);
+    info!(
+        "grow_set: seed={} len={} score={}",
+        seed,
+        result.len(),
+        result.score
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::MovementWeights;
+    use crate::types::track::Track;
+
+    #[test]
+    fn grows_from_the_seed_within_the_constraints() {
+        let library = vec![
+            Track::from_pair("a", "3A").with_bpm(174.0),
+            Track::from_pair("seed", "8A")
+                .with_bpm(174.0)
+                .with_energy(0.5),
+            Track::from_pair("b", "9A").with_bpm(174.0).with_energy(0.6),
+            Track::from_pair("too fast", "10A").with_bpm(140.0),
+            Track::from_pair("c", "10A")
+                .with_bpm(175.0)
+                .with_energy(0.7),
+            Track::from_pair("too loud", "11A")
+                .with_bpm(174.0)
+                .with_energy(1.0),
+            Track::from_pair("d", "11A")
+                .with_bpm(174.0)
+                .with_energy(0.7),
+            Track::new(None, "unknown", "", None),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&library, &weights);
+        request.keyless = KeylessPolicy::Append;
+        request.constraints.max_bpm_step = Some(4.0);
+        request.constraints.max_energy_step = Some(0.2);
+
+        let set = grow_set(&request, 1, 4);
+        assert_eq!(set.order, vec![1, 2, 4, 6]);
+        assert_eq!(set.score, 3 * weights.energy_boost);
+        assert!(set.keyless.is_empty());
+        assert_eq!(grow_set(&request, 1, 2).order, vec![1, 2]);
+    }
+
+    #[test]
+    fn an_isolated_seed_is_a_set_of_its_own() {
+        let library = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("seed", "3B"),
+            Track::new(None, "unknown", "", None),
+        ];
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&library, &weights);
+
+        let set = grow_set(&request, 1, 4);
+        assert_eq!(set.order, vec![1]);
+        assert_eq!(set.score, 0);
+        assert!(set.transitions.is_empty());
+        assert_eq!(grow_set(&request, 2, 4).order, vec![2]);
+    }
+}
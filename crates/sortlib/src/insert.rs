@@ -0,0 +1,230 @@
+use loggit::debug;
+
+use crate::search::{ScoredList, SearchSpace};
+use crate::solver::{KeylessHandling, SortRequest, SortResult};
+
+/// Where a new track went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    /// index into [`SortRequest::tracks`]
+    pub track: usize,
+    /// position of the track in the final order
+    pub position: usize,
+    /// semitones the track is pitch-shifted by
+    pub shift: i8,
+    /// score the set gained when the track was inserted
+    pub gain: i32,
+}
+
+/// A new track that fits nowhere in the set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blocked {
+    /// index into [`SortRequest::tracks`]
+    pub track: usize,
+    /// tracks of the set any one of which, taken out, would leave a valid
+    /// slot for `track`, best resulting score first; empty when removing a
+    /// single track is not enough or the set itself is invalid
+    pub would_move: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Insertion {
+    /// the set with every placed track inserted
+    pub result: SortResult,
+    /// placed tracks, in the order they were inserted
+    pub placed: Vec<Placement>,
+    pub blocked: Vec<Blocked>,
+}
+
+/// Inserts `new_tracks` into `set`, an order of `request.tracks` such as a
+/// previous [`SortResult`], without reordering the tracks already in it.
+///
+/// Tracks are placed one at a time, always taking the insertion that leaves
+/// the best scoring set among the ones where every transition stays valid.
+/// The set itself has to satisfy the request, otherwise nothing is placed.
+/// Keyless tracks appended to the set stay at its end.
+pub fn insert_tracks(request: &SortRequest, set: &SortResult, new_tracks: &[usize]) -> Insertion {
+    let space = SearchSpace::new(request);
+    let appended: Vec<usize> = set
+        .keyless
+        .iter()
+        .filter(|keyless| keyless.handling == KeylessHandling::Appended)
+        .map(|keyless| keyless.track)
+        .collect();
+    let mut list: Vec<usize> = set
+        .order
+        .iter()
+        .zip(&set.shifts)
+        .filter(|(track, _)| !appended.contains(track))
+        .filter_map(|(&track, &shift)| space.node_of(track, shift))
+        .collect();
+    let mut score = space.replay(&list);
+
+    let mut pending: Vec<usize> = new_tracks
+        .iter()
+        .copied()
+        .filter(|track| !set.order.contains(track))
+        .collect();
+    let mut placed = Vec::new();
+    while let Some(current) = score {
+        let best = pending
+            .iter()
+            .flat_map(|&track| best_insertion(&space, &list, track))
+            .max_by_key(|&(_, _, score)| score);
+        let Some((node, position, new_score)) = best else {
+            break;
+        };
+        list.insert(position, node);
+        let track = space.node(node).track;
+        debug!("insert_tracks: track={track} position={position} score={new_score}");
+        pending.retain(|&other| other != track);
+        placed.push(Placement {
+            track,
+            position,
+            shift: space.node(node).shift,
+            gain: new_score - current,
+        });
+        score = Some(new_score);
+    }
+
+    if score.is_none() {
+        debug!("insert_tracks: the set does not satisfy the request");
+    }
+    let blocked = pending
+        .into_iter()
+        .map(|track| Blocked {
+            track,
+            would_move: match score {
+                Some(_) => would_move(&space, &list, track),
+                None => Vec::new(),
+            },
+        })
+        .collect();
+    let order: Vec<usize> = list.iter().map(|&node| space.node(node).track).collect();
+    for placement in &mut placed {
+        placement.position = order.iter().position(|&t| t == placement.track).unwrap();
+    }
+    let result = match score {
+        Some(score) => space.to_result("insert", Some(ScoredList { list, score })),
+        None => set.clone(),
+    };
+    Insertion {
+        result,
+        placed,
+        blocked,
+    }
+}
+
+/// The best scoring `(node, position, score)` for inserting `track` into
+/// `list`, if there is a valid one.
+fn best_insertion(
+    space: &SearchSpace,
+    list: &[usize],
+    track: usize,
+) -> Option<(usize, usize, i32)> {
+    let mut best: Option<(usize, usize, i32)> = None;
+    for node in space.track_nodes(track) {
+        for position in 0..=list.len() {
+            let mut candidate = list.to_vec();
+            candidate.insert(position, node);
+            let Some(score) = space.replay(&candidate) else {
+                continue;
+            };
+            if best.is_none_or(|(_, _, best_score)| score > best_score) {
+                best = Some((node, position, score));
+            }
+        }
+    }
+    best
+}
+
+fn would_move(space: &SearchSpace, list: &[usize], track: usize) -> Vec<usize> {
+    let mut moves: Vec<(usize, i32)> = (0..list.len())
+        .filter_map(|position| {
+            let mut without = list.to_vec();
+            let removed = without.remove(position);
+            let (_, _, score) = best_insertion(space, &without, track)?;
+            Some((space.node(removed).track, score))
+        })
+        .collect();
+    moves.sort_by_key(|&(track, score)| (std::cmp::Reverse(score), track));
+    moves.into_iter().map(|(track, _)| track).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::MovementWeights;
+    use crate::solver::{BeamSolver, KeylessPolicy, Solver};
+    use crate::types::track::Track;
+
+    #[test]
+    fn inserts_between_compatible_neighbours() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "10A"),
+            Track::from_pair("promo", "9A"),
+        ];
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+        let set = BeamSolver.solve(&SortRequest::new(&tracks[..2], &weights));
+        assert_eq!(set.order, vec![0, 1]);
+
+        let insertion = insert_tracks(&request, &set, &[2]);
+        assert_eq!(insertion.result.order, vec![0, 2, 1]);
+        assert_eq!(insertion.placed[0].position, 1);
+        assert_eq!(insertion.result.score, weights.energy_boost * 2);
+        assert_eq!(insertion.placed[0].gain, insertion.result.score - set.score);
+        assert!(insertion.blocked.is_empty());
+    }
+
+    #[test]
+    fn reports_what_blocks_a_track() {
+        let tracks = vec![
+            Track::from_pair("a", "1A"),
+            Track::from_pair("b", "2A"),
+            Track::from_pair("c", "3A"),
+            // only reachable from 1A and only leading to 3A
+            Track::from_pair("promo", "8A"),
+        ];
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+        let mut set_request = SortRequest::new(&tracks[..3], &weights);
+        set_request.constraints.first = Some(0);
+        let set = BeamSolver.solve(&set_request);
+        assert_eq!(set.order, vec![0, 1, 2]);
+
+        let insertion = insert_tracks(&request, &set, &[3]);
+        assert!(insertion.placed.is_empty());
+        assert_eq!(insertion.result.order, set.order);
+        assert_eq!(
+            insertion.blocked,
+            vec![Blocked {
+                track: 3,
+                would_move: vec![1]
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_appended_keyless_tracks_at_the_end() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "10A"),
+            Track::new(None, "unknown", "", None),
+            Track::from_pair("promo", "9A"),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.keyless = KeylessPolicy::Append;
+        let mut set_request = SortRequest::new(&tracks[..3], &weights);
+        set_request.keyless = KeylessPolicy::Append;
+        let set = BeamSolver.solve(&set_request);
+        assert_eq!(set.order, vec![0, 1, 2]);
+
+        let insertion = insert_tracks(&request, &set, &[3]);
+        assert_eq!(insertion.result.order, vec![0, 3, 1, 2]);
+        assert_eq!(insertion.placed[0].position, 1);
+        assert!(insertion.blocked.is_empty());
+    }
+}
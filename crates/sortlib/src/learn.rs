@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use crate::algorithm::{movement_between, Movement, MovementWeights};
+use crate::shuffle::MIN_SCALE;
+use crate::types::track::Track;
+
+/// Weight difference between two movements of which one was played `e` times
+/// as often as the other.
+pub const DEFAULT_SCALE: f32 = 10.0;
+
+/// How often every movement was played across a collection of sets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MovementCounts {
+    counts: HashMap<Movement, usize>,
+    /// transitions between keys that don't mix on the Camelot wheel
+    pub clashes: usize,
+    /// transitions from or to a track without a key, left out of the fit
+    pub unknown: usize,
+}
+
+/// How well a weight profile explains a history of played sets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    /// average log-probability of the played movements, when every movement
+    /// is picked with a probability proportional to `exp(weight / scale)`;
+    /// closer to 0 is better
+    pub log_likelihood: f32,
+    /// average weight of the played movements
+    pub mean_weight: f32,
+    /// share of the transitions with known keys that were valid movements
+    pub coverage: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileFit {
+    pub name: String,
+    pub fit: Fit,
+}
+
+impl MovementCounts {
+    /// Counts the movements of `sets`, each one in play order.
+    pub fn from_sets<S: AsRef<[Track]>>(sets: &[S]) -> Self {
+        let mut counts = Self::default();
+        for set in sets {
+            counts.add_set(set.as_ref());
+        }
+        counts
+    }
+
+    pub fn add_set(&mut self, set: &[Track]) {
+        for step in set.windows(2) {
+            let (Some(start), Some(end)) = (step[0].outro_key(), step[1].intro_key()) else {
+                self.unknown += 1;
+                continue;
+            };
+            match movement_between(start, end) {
+                Some(movement) => *self.counts.entry(movement).or_default() += 1,
+                None => self.clashes += 1,
+            }
+        }
+    }
+
+    pub fn count(&self, movement: Movement) -> usize {
+        self.counts.get(&movement).copied().unwrap_or(0)
+    }
+
+    /// Number of played transitions that were valid movements.
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// The profile that explains the counts best: every movement weighs
+    /// `scale * ln(p / p_min)`, `p` being its smoothed share of the history,
+    /// so the rarest movement weighs 0.
+    pub fn fit(&self, scale: f32) -> MovementWeights {
+        let shares = self.shares();
+        let rarest = shares.iter().copied().fold(f32::INFINITY, f32::min);
+        let mut weights = MovementWeights::default();
+        for (movement, share) in Movement::BUILT_IN.into_iter().zip(shares) {
+            weights.set_weight(movement, (scale * (share / rarest).ln()).round() as i32);
+        }
+        weights
+    }
+
+    /// How well `weights` explain the counts, see [`Fit`]. Scales below
+    /// [`MIN_SCALE`] and invalid ones are raised to it.
+    pub fn fit_of(&self, weights: &MovementWeights, scale: f32) -> Fit {
+        let total = self.total();
+        if total == 0 {
+            return Fit {
+                log_likelihood: 0.0,
+                mean_weight: 0.0,
+                coverage: 0.0,
+            };
+        }
+        // `max` also replaces a NaN scale
+        let scale = scale.max(MIN_SCALE);
+        // relative to the best weight, so large weights can't overflow
+        let best = Movement::BUILT_IN
+            .iter()
+            .map(|&movement| weights.weight(movement))
+            .max()
+            .unwrap_or(0) as f32;
+        let normalizer = best / scale
+            + Movement::BUILT_IN
+                .iter()
+                .map(|&movement| ((weights.weight(movement) as f32 - best) / scale).exp())
+                .sum::<f32>()
+                .ln();
+        let mut log_likelihood = 0.0;
+        let mut weight_sum = 0.0;
+        for movement in Movement::BUILT_IN {
+            let count = self.count(movement) as f32;
+            let weight = weights.weight(movement) as f32;
+            log_likelihood += count * (weight / scale - normalizer);
+            weight_sum += count * weight;
+        }
+        Fit {
+            log_likelihood: log_likelihood / total as f32,
+            mean_weight: weight_sum / total as f32,
+            coverage: total as f32 / (total + self.clashes) as f32,
+        }
+    }
+
+    /// Laplace-smoothed share of every built-in movement, in
+    /// [`Movement::BUILT_IN`] order.
+    fn shares(&self) -> Vec<f32> {
+        let total = (self.total() + Movement::BUILT_IN.len()) as f32;
+        Movement::BUILT_IN
+            .iter()
+            .map(|&movement| (self.count(movement) + 1) as f32 / total)
+            .collect()
+    }
+}
+
+/// Rates every named profile against `counts`, best explanation first.
+pub fn compare_profiles(
+    counts: &MovementCounts,
+    profiles: &[(&str, &MovementWeights)],
+    scale: f32,
+) -> Vec<ProfileFit> {
+    let mut fits: Vec<ProfileFit> = profiles
+        .iter()
+        .map(|(name, weights)| ProfileFit {
+            name: name.to_string(),
+            fit: counts.fit_of(weights, scale),
+        })
+        .collect();
+    fits.sort_by(|a, b| b.fit.log_likelihood.total_cmp(&a.fit.log_likelihood));
+    fits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(keys: &[&str]) -> Vec<Track> {
+        keys.iter().map(|key| Track::from_pair(key, key)).collect()
+    }
+
+    #[test]
+    fn learns_the_movements_actually_played() {
+        let sets = vec![
+            set(&["8A", "9A", "10A", "11A", "12A"]),
+            set(&["3A", "3A", "4A"]),
+            set(&["1A", "5B"]),
+        ];
+        let counts = MovementCounts::from_sets(&sets);
+        assert_eq!(counts.count(Movement::EnergyBoost), 5);
+        assert_eq!(counts.count(Movement::PerfectMatch), 1);
+        assert_eq!(counts.clashes, 1);
+
+        let learned = counts.fit(DEFAULT_SCALE);
+        assert_eq!(learned.energy_boost, 18);
+        assert_eq!(learned.perfect_match, 7);
+        assert_eq!(learned.mood_drop, 0);
+
+        let default = MovementWeights::default();
+        let fits = compare_profiles(
+            &counts,
+            &[("default", &default), ("learned", &learned)],
+            DEFAULT_SCALE,
+        );
+        assert_eq!(fits[0].name, "learned");
+        assert!(fits[0].fit.log_likelihood > fits[1].fit.log_likelihood);
+        assert!((fits[0].fit.coverage - 6.0 / 7.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn large_weights_and_small_scales_stay_finite() {
+        let counts = MovementCounts::from_sets(&[set(&["8A", "9A", "10A"])]);
+        let mut weights = MovementWeights::default();
+        weights.set_weight(Movement::EnergyBoost, 10_000);
+        let fit = counts.fit_of(&weights, DEFAULT_SCALE);
+        assert!(fit.log_likelihood.is_finite());
+        assert!(fit.log_likelihood > -1.0);
+        for scale in [0.0, -1.0, f32::NAN] {
+            let fit = counts.fit_of(&MovementWeights::default(), scale);
+            assert!(fit.log_likelihood.is_finite(), "scale {scale}");
+        }
+    }
+}
@@ -1,4 +1,22 @@
 pub mod algorithm;
+pub mod bridge;
+pub mod community;
+pub mod evaluate;
+pub mod graph;
+pub mod grow;
+pub mod insert;
+pub mod learn;
+pub mod matrix;
+pub mod profiles;
+pub mod replan;
+pub mod rules;
+pub mod schedule;
+pub mod scorer;
+mod search;
+pub mod shuffle;
+pub mod solver;
+pub mod suggest;
+pub mod tempo;
 pub mod types;
 
 #[cfg(test)]
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use crate::algorithm::{Movement, MovementWeights};
+use crate::rules::TransitionRules;
+use crate::types::track::Track;
+
+/// A track one can mix into, see [`CompatibilityMatrix::neighbours`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbour {
+    pub track: usize,
+    pub movement: Movement,
+    pub weight: i32,
+}
+
+/// Which track mixes into which and how, for a whole library.
+///
+/// Tracks keep their index for as long as they are in the matrix: removing
+/// one leaves its slot empty instead of moving the ones after it.
+#[derive(Debug, Clone)]
+pub struct CompatibilityMatrix {
+    rules: TransitionRules,
+    tracks: Vec<Option<Track>>,
+    /// movement and weight of every transition, by outgoing track
+    outgoing: Vec<HashMap<usize, (Movement, i32)>>,
+}
+
+impl CompatibilityMatrix {
+    /// The matrix of `tracks` under the Camelot rules.
+    pub fn new(tracks: &[Track], weights: &MovementWeights) -> Self {
+        Self::with_rules(tracks, TransitionRules::camelot(weights))
+    }
+
+    pub fn with_rules(tracks: &[Track], rules: TransitionRules) -> Self {
+        let mut matrix = Self {
+            rules,
+            tracks: Vec::new(),
+            outgoing: Vec::new(),
+        };
+        for track in tracks {
+            matrix.add(track.clone());
+        }
+        matrix
+    }
+
+    /// Number of slots, removed tracks included.
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// The track at `index`, `None` once it was removed.
+    pub fn track(&self, index: usize) -> Option<&Track> {
+        self.tracks.get(index)?.as_ref()
+    }
+
+    /// How `from` mixes into `to`, `None` when it doesn't.
+    pub fn movement(&self, from: usize, to: usize) -> Option<Movement> {
+        self.entry(from, to).map(|(movement, _)| movement)
+    }
+
+    pub fn weight(&self, from: usize, to: usize) -> Option<i32> {
+        self.entry(from, to).map(|(_, weight)| weight)
+    }
+
+    /// Every track `index` mixes into, best weight first.
+    pub fn neighbours(&self, index: usize) -> Vec<Neighbour> {
+        let Some(outgoing) = self.outgoing.get(index) else {
+            return Vec::new();
+        };
+        let mut neighbours: Vec<Neighbour> = outgoing
+            .iter()
+            .map(|(&track, &(movement, weight))| Neighbour {
+                track,
+                movement,
+                weight,
+            })
+            .collect();
+        neighbours.sort_by_key(|neighbour| (std::cmp::Reverse(neighbour.weight), neighbour.track));
+        neighbours
+    }
+
+    /// Adds `track` and its transitions from and to every other track,
+    /// returning its index.
+    pub fn add(&mut self, track: Track) -> usize {
+        let index = self.tracks.len();
+        let mut outgoing = HashMap::new();
+        for (other, value) in self.tracks.iter().enumerate() {
+            let Some(value) = value else { continue };
+            if let Some(found) = self.find(&track, value) {
+                outgoing.insert(other, found);
+            }
+            if let Some(found) = self.find(value, &track) {
+                self.outgoing[other].insert(index, found);
+            }
+        }
+        self.tracks.push(Some(track));
+        self.outgoing.push(outgoing);
+        index
+    }
+
+    /// Removes the track at `index` and its transitions; the other tracks
+    /// keep their indices.
+    pub fn remove(&mut self, index: usize) -> Option<Track> {
+        let track = self.tracks.get_mut(index)?.take()?;
+        self.outgoing[index].clear();
+        for outgoing in &mut self.outgoing {
+            outgoing.remove(&index);
+        }
+        Some(track)
+    }
+
+    fn entry(&self, from: usize, to: usize) -> Option<(Movement, i32)> {
+        self.outgoing.get(from)?.get(&to).copied()
+    }
+
+    fn find(&self, from: &Track, to: &Track) -> Option<(Movement, i32)> {
+        self.rules.find(from.outro_key()?, to.intro_key()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_lookups_and_updates_incrementally() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "9A"),
+            Track::from_pair("c", "3B"),
+        ];
+        let weights = MovementWeights::default();
+        let mut matrix = CompatibilityMatrix::new(&tracks, &weights);
+        assert_eq!(matrix.movement(0, 1), Some(Movement::EnergyBoost));
+        assert_eq!(matrix.weight(1, 0), Some(weights.energy_drop));
+        assert_eq!(matrix.movement(0, 2), None);
+        assert!(matrix.neighbours(2).is_empty());
+
+        let d = matrix.add(Track::from_pair("d", "8A"));
+        assert_eq!(d, 3);
+        let neighbours = matrix.neighbours(0);
+        assert_eq!(neighbours[0].track, d);
+        assert_eq!(neighbours[0].movement, Movement::PerfectMatch);
+        assert_eq!(matrix.movement(1, d), Some(Movement::EnergyDrop));
+
+        assert_eq!(matrix.remove(1).unwrap().name(), "b");
+        assert_eq!(matrix.movement(0, 1), None);
+        assert_eq!(matrix.neighbours(0).len(), 1);
+        assert_eq!(matrix.track(d).unwrap().name(), "d");
+        assert!(matrix.remove(1).is_none());
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::algorithm::{Movement, MovementWeights};
+
+/// Names of the built-in profiles, see [`MovementWeights::preset`].
+pub const PROFILE_NAMES: [&str; 5] = ["default", "safe", "adventurous", "energy-up", "mood-only"];
+
+impl MovementWeights {
+    /// One of the built-in profiles listed in [`PROFILE_NAMES`]:
+    ///
+    /// - `safe` sticks to perfect matches and neighbouring keys
+    /// - `adventurous` favours the bigger jumps around the wheel
+    /// - `energy-up` rewards moves that lift the energy and punishes drops
+    /// - `mood-only` moves between minor and major as much as possible
+    pub fn preset(name: &str) -> Option<Self> {
+        let weights = |values: [i32; 11]| {
+            let mut weights = MovementWeights::default();
+            for (movement, value) in Movement::BUILT_IN.into_iter().zip(values) {
+                weights.set_weight(movement, value);
+            }
+            weights
+        };
+        // in Movement::BUILT_IN order: perfect match, energy boost, energy
+        // drop, energy switch, mood boost, mood drop, energy raise, dom key,
+        // sub dom key, tone boost, tone drop
+        match name.trim().to_ascii_lowercase().as_str() {
+            "default" => Some(Self::default()),
+            "safe" => Some(weights([40, 15, 15, 15, -5, -5, -10, 5, 5, -10, -10])),
+            "adventurous" => Some(weights([5, 10, 10, 15, 20, 20, 20, 15, 15, 15, 15])),
+            "energy-up" => Some(weights([10, 30, -10, 5, 15, -5, 30, 20, 0, 20, -10])),
+            "mood-only" => Some(weights([10, 0, 0, 25, 30, 30, 0, 5, 5, 0, 0])),
+            _ => None,
+        }
+    }
+}
+
+/// Built-in profiles plus the ones loaded from config files, by name.
+#[derive(Debug, Clone)]
+pub struct Profiles {
+    profiles: BTreeMap<String, MovementWeights>,
+}
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Profiles {
+    pub fn builtin() -> Self {
+        let profiles = PROFILE_NAMES
+            .iter()
+            .filter_map(|&name| Some((name.to_string(), MovementWeights::preset(name)?)))
+            .collect();
+        Self { profiles }
+    }
+
+    /// Loads a profile file on top of the built-in profiles, as JSON when
+    /// the file ends in `.json` and as TOML otherwise.
+    pub fn load(path: &Path) -> Result<Self, ProfileError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| ProfileError::Io(format!("{}: {err}", path.display())))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&text),
+            _ => Self::from_toml(&text),
+        }
+    }
+
+    /// Parses profiles such as
+    ///
+    /// ```toml
+    /// [profiles.warmup]
+    /// base = "safe"             # any other profile, "default" if missing
+    /// perfect_match = 50
+    /// energy_raise = -20
+    /// ```
+    pub fn from_toml(text: &str) -> Result<Self, ProfileError> {
+        let config: ProfileConfig =
+            toml::from_str(text).map_err(|err| ProfileError::Parse(err.to_string()))?;
+        Self::builtin().extended(config)
+    }
+
+    /// Parses the JSON form of [`Profiles::from_toml`], e.g.
+    /// `{"profiles": {"warmup": {"base": "safe", "perfect_match": 50}}}`.
+    pub fn from_json(text: &str) -> Result<Self, ProfileError> {
+        let config: ProfileConfig =
+            serde_json::from_str(text).map_err(|err| ProfileError::Parse(err.to_string()))?;
+        Self::builtin().extended(config)
+    }
+
+    /// The profile called `name`, ignoring case.
+    pub fn get(&self, name: &str) -> Result<&MovementWeights, ProfileError> {
+        self.profiles
+            .get(&name.trim().to_ascii_lowercase())
+            .ok_or_else(|| ProfileError::UnknownProfile(name.to_string()))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    fn extended(mut self, config: ProfileConfig) -> Result<Self, ProfileError> {
+        for (name, entry) in &config.profiles {
+            let unknown: Vec<String> = entry
+                .weights
+                .keys()
+                .filter(|field| Movement::from_name(field).is_none())
+                .cloned()
+                .collect();
+            if !unknown.is_empty() {
+                return Err(ProfileError::UnknownFields {
+                    profile: name.clone(),
+                    fields: unknown,
+                });
+            }
+        }
+
+        // profiles may be based on each other in any order, and a base the
+        // file defines wins over the built-in profile of the same name
+        let mut unresolved: HashSet<String> = config
+            .profiles
+            .keys()
+            .map(|name| name.trim().to_ascii_lowercase())
+            .collect();
+        let mut pending: Vec<(String, ProfileEntry)> = config.profiles.into_iter().collect();
+        while !pending.is_empty() {
+            let before = pending.len();
+            let mut waiting = Vec::new();
+            for (name, entry) in pending {
+                let key = name.trim().to_ascii_lowercase();
+                let base = entry.base.as_deref().unwrap_or("default");
+                let base_key = base.trim().to_ascii_lowercase();
+                // a profile based on its own name extends the built-in one
+                let waits = base_key != key && unresolved.contains(&base_key);
+                let Some(base) = self.get(base).ok().filter(|_| !waits) else {
+                    waiting.push((name, entry));
+                    continue;
+                };
+                let mut weights = base.clone();
+                for (field, value) in &entry.weights {
+                    weights.set_weight(Movement::from_name(field).unwrap(), *value);
+                }
+                unresolved.remove(&key);
+                self.profiles.insert(key, weights);
+            }
+            if waiting.len() == before {
+                let (profile, entry) = waiting.swap_remove(0);
+                return Err(ProfileError::UnknownBase {
+                    profile,
+                    base: entry.base.unwrap_or_default(),
+                });
+            }
+            pending = waiting;
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileConfig {
+    #[serde(default)]
+    profiles: BTreeMap<String, ProfileEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileEntry {
+    base: Option<String>,
+    /// every other key, validated against the movement names
+    #[serde(flatten)]
+    weights: BTreeMap<String, i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileError {
+    Io(String),
+    Parse(String),
+    UnknownProfile(String),
+    UnknownBase {
+        profile: String,
+        base: String,
+    },
+    UnknownFields {
+        profile: String,
+        fields: Vec<String>,
+    },
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Io(value) => write!(f, "cannot read profiles {value}"),
+            ProfileError::Parse(value) => write!(f, "invalid profiles file: {value}"),
+            ProfileError::UnknownProfile(value) => write!(f, "unknown weight profile {value}"),
+            ProfileError::UnknownBase { profile, base } => {
+                write!(f, "profile {profile} is based on unknown profile {base}")
+            }
+            ProfileError::UnknownFields { profile, fields } => {
+                write!(
+                    f,
+                    "profile {profile} has unknown fields: {}",
+                    fields.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_profiles_from_toml_and_json() {
+        let toml = r#"
+            [profiles.warmup]
+            base = "safe"
+            perfect_match = 50
+
+            [profiles.peak]
+            base = "warmup"
+            EnergyRaise = 25
+        "#;
+        let profiles = Profiles::from_toml(toml).unwrap();
+        let safe = MovementWeights::preset("safe").unwrap();
+        let warmup = profiles.get("warmup").unwrap();
+        assert_eq!(warmup.perfect_match, 50);
+        assert_eq!(warmup.energy_boost, safe.energy_boost);
+        assert_eq!(profiles.get("Peak").unwrap().energy_raise, 25);
+        assert_eq!(profiles.get("peak").unwrap().perfect_match, 50);
+
+        let json = r#"{"profiles": {"warmup": {"base": "safe", "perfect_match": 50}}}"#;
+        let from_json = Profiles::from_json(json).unwrap();
+        assert_eq!(from_json.get("warmup").unwrap(), warmup);
+        assert!(from_json.get("energy-up").is_ok());
+    }
+
+    #[test]
+    fn redefined_builtins_are_the_base_of_the_file_profiles() {
+        let toml = r#"
+            [profiles.peak]
+            base = "safe"
+            energy_raise = 25
+
+            [profiles.safe]
+            base = "safe"
+            perfect_match = 50
+        "#;
+        let profiles = Profiles::from_toml(toml).unwrap();
+        let safe = profiles.get("safe").unwrap();
+        assert_eq!(safe.perfect_match, 50);
+        assert_eq!(
+            safe.energy_boost,
+            MovementWeights::preset("safe").unwrap().energy_boost
+        );
+        let peak = profiles.get("peak").unwrap();
+        assert_eq!(peak.perfect_match, 50);
+        assert_eq!(peak.energy_raise, 25);
+    }
+
+    #[test]
+    fn errors_name_the_unknown_fields() {
+        let toml = r#"
+            [profiles.typo]
+            perfect_mach = 50
+            energy_bost = 20
+            tone_drop = 5
+        "#;
+        let err = Profiles::from_toml(toml).unwrap_err();
+        assert_eq!(
+            err,
+            ProfileError::UnknownFields {
+                profile: "typo".to_string(),
+                fields: vec!["energy_bost".to_string(), "perfect_mach".to_string()],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "profile typo has unknown fields: energy_bost, perfect_mach"
+        );
+        assert!(matches!(
+            Profiles::builtin().get("chill"),
+            Err(ProfileError::UnknownProfile(_))
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashSet;
+
+use loggit::{debug, info};
+
+use crate::algorithm::beam_search_from;
+use crate::search::{ScoredList, SearchSpace, Steering};
+use crate::solver::{SortRequest, SortResult};
+
+#[derive(Debug, Clone)]
+pub struct ReplanOptions {
+    /// tracks the rest of the set has to include, e.g. crowd requests
+    pub required: Vec<usize>,
+    /// seconds left to fill once the current track is over, unlimited when
+    /// `None`
+    pub remaining: Option<f32>,
+    /// score added for every transition of the old plan the new one keeps
+    pub keep_bonus: i32,
+    /// score added for every required track, large enough to outweigh any
+    /// transition
+    pub required_bonus: i32,
+}
+
+impl Default for ReplanOptions {
+    fn default() -> Self {
+        Self {
+            required: Vec::new(),
+            remaining: None,
+            keep_bonus: 15,
+            required_bonus: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Replanned {
+    /// the played tracks followed by the new rest of the set; the score
+    /// leaves out the keep and required bonuses
+    pub result: SortResult,
+    /// transitions after the current track that were already in the old plan
+    pub kept: usize,
+    /// required tracks that did not fit
+    pub missing: Vec<usize>,
+}
+
+/// Re-optimizes the rest of `plan` after `played` (oldest first, `current`
+/// may be its last entry) and `current`, which stay as they were played.
+///
+/// The rest is searched with the request's scoring, leaning towards the
+/// transitions of `plan`; `request.constraints.first` is ignored.
+pub fn replan(
+    request: &SortRequest,
+    plan: &SortResult,
+    played: &[usize],
+    current: usize,
+    options: &ReplanOptions,
+) -> Replanned {
+    let mut prefix: Vec<usize> = played.iter().copied().filter(|&t| t != current).collect();
+    prefix.push(current);
+
+    let mut request = request.clone();
+    request.constraints.first = None;
+    if let Some(remaining) = options.remaining {
+        let played_duration: f32 = prefix
+            .iter()
+            .filter_map(|&track| request.tracks[track].duration())
+            .sum();
+        let limit = played_duration + remaining;
+        request.constraints.max_duration = Some(match request.constraints.max_duration {
+            Some(max_duration) => max_duration.min(limit),
+            None => limit,
+        });
+    }
+
+    let mut steering = Steering::default();
+    for transition in &plan.transitions {
+        steering
+            .transitions
+            .insert((transition.from, transition.to), options.keep_bonus);
+    }
+    for &track in &options.required {
+        steering.tracks.insert(track, options.required_bonus);
+    }
+
+    let result = match plan_rest(&request, plan, &prefix, &steering) {
+        Some(result) => result,
+        None => {
+            // nothing after the played tracks leads back to the first one
+            debug!("replan: the set can't close, replanning it open");
+            request.constraints.cyclic = false;
+            plan_rest(&request, plan, &prefix, &steering)
+                .unwrap_or_else(|| SortResult::empty("replan"))
+        }
+    };
+
+    let planned: HashSet<(usize, usize)> = plan
+        .transitions
+        .iter()
+        .map(|transition| (transition.from, transition.to))
+        .collect();
+    let kept = result.order[(prefix.len() - 1).min(result.order.len())..]
+        .windows(2)
+        .filter(|step| planned.contains(&(step[0], step[1])))
+        .count();
+    let missing: Vec<usize> = options
+        .required
+        .iter()
+        .copied()
+        .filter(|track| !result.order.contains(track))
+        .collect();
+    info!(
+        "replan: len={} kept={} missing={}",
+        result.len(),
+        kept,
+        missing.len()
+    );
+    Replanned {
+        result,
+        kept,
+        missing,
+    }
+}
+
+/// The played `prefix` followed by the best rest, `None` when a cyclic set
+/// can't close.
+fn plan_rest(
+    request: &SortRequest,
+    plan: &SortResult,
+    prefix: &[usize],
+    steering: &Steering,
+) -> Option<SortResult> {
+    let space = SearchSpace::new(request);
+    let steered = SearchSpace::new(request).with_steering(steering.clone());
+    let shift_in_plan = |track: usize| {
+        plan.order
+            .iter()
+            .position(|&t| t == track)
+            .map_or(0, |position| plan.shifts[position])
+    };
+    let list: Vec<usize> = prefix
+        .iter()
+        .filter_map(|&track| {
+            space
+                .node_of(track, shift_in_plan(track))
+                .or_else(|| space.track_node(track))
+        })
+        .collect();
+    let opening = ScoredList {
+        score: steered.replay_played(&list),
+        list,
+    };
+    // an open set can always stop after the played tracks
+    let fallback = (!request.constraints.cyclic).then(|| opening.clone());
+    let best = beam_search_from(&steered, vec![opening], request.budget.beam_width).or(fallback)?;
+
+    // the played steps score as played, the closing one as the result shows it
+    let score = space.replay_played(&best.list) + space.close(&best.list).unwrap_or(0);
+    Some(space.to_result(
+        "replan",
+        Some(ScoredList {
+            list: best.list,
+            score,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::MovementWeights;
+    use crate::solver::{BeamSolver, Solver};
+    use crate::types::track::Track;
+
+    fn planned_set(tracks: &[Track], weights: &MovementWeights) -> SortResult {
+        let mut request = SortRequest::new(tracks, weights);
+        request.constraints.first = Some(0);
+        BeamSolver.solve(&request)
+    }
+
+    #[test]
+    fn keeps_the_prefix_and_fits_required_tracks() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "9A"),
+            Track::from_pair("c", "10A"),
+            Track::from_pair("d", "11A"),
+            Track::from_pair("e", "12A"),
+            Track::from_pair("request", "10B"),
+        ];
+        let weights = MovementWeights::default();
+        let plan = planned_set(&tracks[..5], &weights);
+        assert_eq!(plan.order, vec![0, 1, 2, 3, 4]);
+
+        let request = SortRequest::new(&tracks, &weights);
+        let options = ReplanOptions {
+            required: vec![5],
+            ..ReplanOptions::default()
+        };
+        let replanned = replan(&request, &plan, &[0], 1, &options);
+        assert_eq!(replanned.result.order[..2], [0, 1]);
+        assert_eq!(replanned.result.len(), 6);
+        assert!(replanned.missing.is_empty());
+        assert_eq!(replanned.kept, 2);
+        assert_eq!(replanned.result.score, weights.energy_boost * 5);
+    }
+
+    #[test]
+    fn remaining_time_limits_the_rest() {
+        let tracks: Vec<Track> = ["8A", "9A", "10A", "11A", "12A", "10B"]
+            .iter()
+            .map(|key| Track::from_pair(key, key).with_duration(300.0))
+            .collect();
+        let weights = MovementWeights::default();
+        let plan = planned_set(&tracks[..5], &weights);
+
+        let request = SortRequest::new(&tracks, &weights);
+        let options = ReplanOptions {
+            remaining: Some(600.0),
+            ..ReplanOptions::default()
+        };
+        let replanned = replan(&request, &plan, &[0, 1], 1, &options);
+        assert_eq!(replanned.result.order, vec![0, 1, 2, 3]);
+        assert_eq!(replanned.kept, 2);
+    }
+
+    #[test]
+    fn cyclic_sets_close_when_they_can() {
+        let tracks: Vec<Track> = ["8A", "9A", "9B", "8B", "3B"]
+            .iter()
+            .map(|key| Track::from_pair(key, key))
+            .collect();
+        let weights = MovementWeights::default();
+        let plan = planned_set(&tracks[..4], &weights);
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.cyclic = true;
+
+        let replanned = replan(&request, &plan, &[0], 1, &ReplanOptions::default());
+        let result = &replanned.result;
+        assert_eq!(result.order[..2], [0, 1]);
+        assert_eq!(result.transitions.len(), result.len());
+        let total: i32 = result.transitions.iter().map(|t| t.weight).sum();
+        assert_eq!(result.score, total);
+
+        // nothing leads from 3B back into the set
+        let stuck = replan(&request, &plan, &[], 4, &ReplanOptions::default());
+        assert_eq!(stuck.result.order, vec![4]);
+        assert!(stuck.result.transitions.is_empty());
+        assert_eq!(stuck.result.score, 0);
+    }
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::algorithm::{forward_delta, Movement, MovementWeights};
+use crate::types::key::Key;
+
+/// The Camelot wheel moves as `(movement, letter change, forward wheel delta)`.
+const CAMELOT: [(Movement, bool, u8); 11] = [
+    (Movement::PerfectMatch, false, 0),
+    (Movement::EnergyBoost, false, 1),
+    (Movement::EnergyDrop, false, 11),
+    (Movement::ToneBoost, false, 2),
+    (Movement::ToneDrop, false, 10),
+    (Movement::EnergyRaise, false, 7),
+    (Movement::EnergySwitch, true, 0),
+    (Movement::DomKey, true, 1),
+    (Movement::SubDomKey, true, 11),
+    (Movement::MoodBoost, true, 3),
+    (Movement::MoodDrop, true, 9),
+];
+
+/// What a pair of keys has to look like for a rule to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulePattern {
+    /// steps forward on the wheel (0..12), with or without an A/B switch
+    Wheel { letter_change: bool, delta: u8 },
+    /// semitones up between the tonics (0..12), with or without a mode switch
+    Semitones { letter_change: bool, interval: u8 },
+}
+
+impl RulePattern {
+    pub fn matches(&self, start: &Key, end: &Key) -> bool {
+        let letter_changed = start.letter() != end.letter();
+        match *self {
+            RulePattern::Wheel {
+                letter_change,
+                delta,
+            } => {
+                letter_change == letter_changed
+                    && forward_delta(start.number(), end.number()) == delta
+            }
+            RulePattern::Semitones {
+                letter_change,
+                interval,
+            } => {
+                let up = (end.pitch_class() + 12 - start.pitch_class()) % 12;
+                letter_change == letter_changed && up == interval
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionRule {
+    /// name shown in results, a built-in [`Movement`] name or a custom one
+    pub name: String,
+    pub pattern: RulePattern,
+    /// weight of the move; `None` takes it from [`MovementWeights`]
+    pub weight: Option<i32>,
+}
+
+/// An ordered table of transition rules; the first matching rule wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionRules {
+    rules: Vec<TransitionRule>,
+    weights: MovementWeights,
+}
+
+impl TransitionRules {
+    pub fn new(rules: Vec<TransitionRule>, weights: &MovementWeights) -> Self {
+        Self {
+            rules,
+            weights: weights.clone(),
+        }
+    }
+
+    /// The standard Camelot wheel rules, weighted by `weights`.
+    pub fn camelot(weights: &MovementWeights) -> Self {
+        let rules = CAMELOT
+            .iter()
+            .map(|&(movement, letter_change, delta)| TransitionRule {
+                name: movement.name().to_string(),
+                pattern: RulePattern::Wheel {
+                    letter_change,
+                    delta,
+                },
+                weight: None,
+            })
+            .collect();
+        Self::new(rules, weights)
+    }
+
+    /// Reads a rule table from a TOML file, see [`TransitionRules::from_toml`].
+    pub fn load(path: &Path, weights: &MovementWeights) -> Result<Self, RulesError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| RulesError::Io(format!("{}: {err}", path.display())))?;
+        Self::from_toml(&text, weights)
+    }
+
+    /// Parses a rule table such as
+    ///
+    /// ```toml
+    /// base = "camelot"          # or "none" to start from an empty table
+    /// disable = ["EnergyRaise"]
+    ///
+    /// [[rule]]
+    /// name = "Diagonal"
+    /// letter_change = true
+    /// delta = 1                 # wheel steps, negative values go backwards
+    /// weight = 8
+    ///
+    /// [[rule]]
+    /// name = "WholeToneUp"
+    /// semitones = 2
+    /// weight = 2
+    /// ```
+    ///
+    /// Rules from the file are tried before the base table, and replace base
+    /// rules with the same name.
+    pub fn from_toml(text: &str, weights: &MovementWeights) -> Result<Self, RulesError> {
+        let config: RulesConfig =
+            toml::from_str(text).map_err(|err| RulesError::Parse(err.to_string()))?;
+
+        let mut base = match config.base.as_deref().unwrap_or("camelot") {
+            "camelot" => Self::camelot(weights).rules,
+            "none" => Vec::new(),
+            other => return Err(RulesError::UnknownBase(other.to_string())),
+        };
+        for name in &config.disable {
+            let before = base.len();
+            base.retain(|rule| !same_name(&rule.name, name));
+            if base.len() == before {
+                return Err(RulesError::UnknownRule(name.clone()));
+            }
+        }
+
+        let mut rules = Vec::new();
+        for entry in config.rule {
+            let pattern = entry.pattern()?;
+            base.retain(|rule| !same_name(&rule.name, &entry.name));
+            rules.push(TransitionRule {
+                name: entry.name,
+                pattern,
+                weight: entry.weight,
+            });
+        }
+        rules.extend(base);
+
+        Ok(Self::new(rules, weights))
+    }
+
+    pub fn rules(&self) -> &[TransitionRule] {
+        &self.rules
+    }
+
+    /// Removes the rule called `name`, returning whether it existed.
+    pub fn disable(&mut self, name: &str) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|rule| !same_name(&rule.name, name));
+        self.rules.len() != before
+    }
+
+    /// The movement and weight of the first rule matching `start -> end`.
+    pub fn find(&self, start: &Key, end: &Key) -> Option<(Movement, i32)> {
+        self.rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.pattern.matches(start, end))
+            .map(|(index, rule)| {
+                let movement = self.movement_of(index);
+                let weight = rule.weight.unwrap_or_else(|| self.weights.weight(movement));
+                (movement, weight)
+            })
+    }
+
+    /// Display name of a movement produced by this table.
+    pub fn name_of(&self, movement: Movement) -> &str {
+        match movement {
+            Movement::Custom(index) => self
+                .rules
+                .get(index as usize)
+                .map(|rule| rule.name.as_str())
+                .unwrap_or("Custom"),
+            movement => movement.name(),
+        }
+    }
+
+    fn movement_of(&self, index: usize) -> Movement {
+        Movement::from_name(&self.rules[index].name).unwrap_or(Movement::Custom(index as u16))
+    }
+}
+
+fn same_name(left: &str, right: &str) -> bool {
+    left.eq_ignore_ascii_case(right)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesConfig {
+    base: Option<String>,
+    #[serde(default)]
+    disable: Vec<String>,
+    #[serde(default)]
+    rule: Vec<RuleEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    name: String,
+    #[serde(default)]
+    letter_change: bool,
+    delta: Option<i32>,
+    semitones: Option<i32>,
+    weight: Option<i32>,
+}
+
+impl RuleEntry {
+    fn pattern(&self) -> Result<RulePattern, RulesError> {
+        let letter_change = self.letter_change;
+        match (self.delta, self.semitones) {
+            (Some(delta), None) => Ok(RulePattern::Wheel {
+                letter_change,
+                delta: delta.rem_euclid(12) as u8,
+            }),
+            (None, Some(semitones)) => Ok(RulePattern::Semitones {
+                letter_change,
+                interval: semitones.rem_euclid(12) as u8,
+            }),
+            _ => Err(RulesError::InvalidRule(self.name.clone())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RulesError {
+    Io(String),
+    Parse(String),
+    UnknownBase(String),
+    UnknownRule(String),
+    InvalidRule(String),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Io(value) => write!(f, "cannot read rules {value}"),
+            RulesError::Parse(value) => write!(f, "invalid rules file: {value}"),
+            RulesError::UnknownBase(value) => write!(f, "unknown rules base {value}"),
+            RulesError::UnknownRule(value) => write!(f, "cannot disable unknown rule {value}"),
+            RulesError::InvalidRule(value) => {
+                write!(f, "rule {value} needs exactly one of delta or semitones")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::movement_between;
+    use crate::types::key::KeyLetter;
+
+    fn all_keys() -> Vec<Key> {
+        (1..=12)
+            .flat_map(|number| {
+                [KeyLetter::A, KeyLetter::B].map(|letter| Key::new(number, letter).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn camelot_table_matches_movement_between() {
+        let rules = TransitionRules::camelot(&MovementWeights::default());
+        for start in all_keys() {
+            for end in all_keys() {
+                let found = rules.find(&start, &end).map(|(movement, _)| movement);
+                assert_eq!(found, movement_between(&start, &end), "{start} -> {end}");
+            }
+        }
+    }
+
+    #[test]
+    fn config_adds_and_disables_rules() {
+        let weights = MovementWeights::default();
+        let rules = TransitionRules::from_toml(
+            r#"
+            disable = ["EnergyRaise"]
+
+            [[rule]]
+            name = "Diagonal"
+            letter_change = true
+            delta = 1
+            weight = 8
+
+            [[rule]]
+            name = "WholeToneDown"
+            semitones = -2
+            weight = 1
+            "#,
+            &weights,
+        )
+        .unwrap();
+
+        let key = |value: &str| Key::from_camelot(value).unwrap();
+        let (movement, weight) = rules.find(&key("8A"), &key("9B")).unwrap();
+        assert_eq!((rules.name_of(movement), weight), ("Diagonal", 8));
+        assert!(rules.find(&key("8A"), &key("3A")).is_none());
+        // 10A (B minor) is two semitones below 12A (C# minor), matched before ToneDrop
+        let (movement, _) = rules.find(&key("12A"), &key("10A")).unwrap();
+        assert_eq!(rules.name_of(movement), "WholeToneDown");
+        let (movement, weight) = rules.find(&key("8A"), &key("8A")).unwrap();
+        assert_eq!((movement, weight), (Movement::PerfectMatch, 35));
+
+        let err = TransitionRules::from_toml("disable = [\"Nope\"]", &weights).unwrap_err();
+        assert_eq!(err, RulesError::UnknownRule("Nope".to_string()));
+    }
+}
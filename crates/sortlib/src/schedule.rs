@@ -0,0 +1,140 @@
+use crate::algorithm::{Movement, MovementWeights};
+use crate::profiles::{ProfileError, Profiles};
+
+/// How a [`WeightSchedule`] moves from one set of weights to the next.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Blend {
+    /// every set of weights holds until the next one starts
+    #[default]
+    Phases,
+    /// weights change linearly between one point and the next
+    Interpolate,
+}
+
+/// Movement weights that change over the course of a set, by progress from
+/// 0 at the first track to 1 at the last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightSchedule {
+    /// progress every set of weights applies at, in order
+    points: Vec<(f32, MovementWeights)>,
+    blend: Blend,
+}
+
+impl WeightSchedule {
+    /// Phase segments, each starting at the given progress; the first one
+    /// also covers the start of the set.
+    pub fn phases(phases: Vec<(f32, MovementWeights)>) -> Self {
+        Self::new(phases, Blend::Phases)
+    }
+
+    /// Weights blended between the given points; before the first and after
+    /// the last, their weights apply unchanged.
+    pub fn interpolated(points: Vec<(f32, MovementWeights)>) -> Self {
+        Self::new(points, Blend::Interpolate)
+    }
+
+    pub fn new(mut points: Vec<(f32, MovementWeights)>, blend: Blend) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { points, blend }
+    }
+
+    /// A schedule of named profiles, e.g. `[(0.0, "safe"), (0.3,
+    /// "energy-up"), (0.8, "default")]`.
+    pub fn from_profiles(
+        profiles: &Profiles,
+        points: &[(f32, &str)],
+        blend: Blend,
+    ) -> Result<Self, ProfileError> {
+        let points = points
+            .iter()
+            .map(|&(progress, name)| Ok((progress, profiles.get(name)?.clone())))
+            .collect::<Result<Vec<_>, ProfileError>>()?;
+        Ok(Self::new(points, blend))
+    }
+
+    /// The weights at `progress`, the default weights for an empty schedule.
+    pub fn weights_at(&self, progress: f32) -> MovementWeights {
+        let after = self.points.partition_point(|(at, _)| *at <= progress);
+        let (Some(before), next) = (after.checked_sub(1), self.points.get(after)) else {
+            return self
+                .points
+                .first()
+                .map(|(_, weights)| weights.clone())
+                .unwrap_or_default();
+        };
+        let (start, from) = &self.points[before];
+        match (self.blend, next) {
+            (Blend::Interpolate, Some((end, to))) => {
+                let t = (progress - start) / (end - start);
+                let mut weights = from.clone();
+                for movement in Movement::BUILT_IN {
+                    let (a, b) = (from.weight(movement) as f32, to.weight(movement) as f32);
+                    weights.set_weight(movement, (a + (b - a) * t).round() as i32);
+                }
+                weights
+            }
+            _ => from.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+    use crate::solver::SortRequest;
+    use crate::types::track::Track;
+
+    fn weights(energy_boost: i32, energy_drop: i32) -> MovementWeights {
+        MovementWeights {
+            energy_boost,
+            energy_drop,
+            ..MovementWeights::default()
+        }
+    }
+
+    #[test]
+    fn scores_every_transition_with_the_weights_of_its_position() {
+        let tracks: Vec<Track> = ["8A", "9A", "10A", "9A", "8A"]
+            .iter()
+            .map(|key| Track::from_pair(key, key))
+            .collect();
+        let default = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &default);
+        assert_eq!(evaluate(&request).score, 40);
+
+        let build_up = weights(30, -10);
+        let cool_down = weights(-10, 30);
+        request.schedule = Some(WeightSchedule::phases(vec![
+            (0.6, cool_down.clone()),
+            (0.0, build_up.clone()),
+        ]));
+        assert_eq!(evaluate(&request).score, 120);
+
+        // transitions end at 0.25, 0.5, 0.75 and 1
+        let schedule = WeightSchedule::interpolated(vec![(0.0, build_up), (1.0, cool_down)]);
+        assert_eq!(schedule.weights_at(0.25).energy_boost, 20);
+        assert_eq!(schedule.weights_at(2.0).energy_drop, 30);
+        request.schedule = Some(schedule);
+        assert_eq!(evaluate(&request).score, 20 + 10 + 20 + 30);
+    }
+
+    #[test]
+    fn builds_schedules_from_profiles() {
+        let profiles = Profiles::builtin();
+        let schedule = WeightSchedule::from_profiles(
+            &profiles,
+            &[(0.0, "safe"), (0.5, "energy-up")],
+            Blend::Phases,
+        )
+        .unwrap();
+        assert_eq!(schedule.weights_at(0.2), *profiles.get("safe").unwrap());
+        assert_eq!(
+            schedule.weights_at(0.5),
+            *profiles.get("energy-up").unwrap()
+        );
+        assert!(
+            WeightSchedule::from_profiles(&profiles, &[(0.0, "chill")], Blend::Phases).is_err()
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::fmt;
+
+use crate::algorithm::Movement;
+use crate::types::artist::normalize;
+use crate::types::track::Track;
+
+/// Names accepted by [`scorer_by_name`].
+pub const SCORER_NAMES: [&str; 5] = ["key", "tempo", "energy", "loudness", "genre"];
+
+/// A transition to be scored, see [`TransitionScorer`].
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'t> {
+    pub from: &'t Track,
+    pub to: &'t Track,
+    pub movement: Movement,
+    /// weight of the movement under the request's rules, weights and
+    /// schedule
+    pub weight: i32,
+}
+
+/// Scores a transition between two tracks. Set as
+/// [`crate::solver::SortRequest::scorer`], the score replaces the weight of
+/// the movement; confidence discounts and transposition penalties still
+/// apply on top.
+///
+/// Any `Fn(&Candidate) -> i32` closure is a scorer, e.g. for house rules:
+///
+/// ```
+/// use sortlib::scorer::{Candidate, KeyScorer, WeightedScorer};
+///
+/// let no_same_artist = |candidate: &Candidate| {
+///     if candidate.from.shares_artist(candidate.to) { -50 } else { 0 }
+/// };
+/// let scorer = WeightedScorer::new()
+///     .with(1.0, KeyScorer)
+///     .with(1.0, no_same_artist);
+/// ```
+pub trait TransitionScorer {
+    fn score(&self, candidate: &Candidate) -> i32;
+}
+
+impl fmt::Debug for dyn TransitionScorer + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TransitionScorer")
+    }
+}
+
+impl<F: Fn(&Candidate) -> i32> TransitionScorer for F {
+    fn score(&self, candidate: &Candidate) -> i32 {
+        self(candidate)
+    }
+}
+
+/// The weight of the movement itself, what transitions score without a
+/// scorer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyScorer;
+
+impl TransitionScorer for KeyScorer {
+    fn score(&self, candidate: &Candidate) -> i32 {
+        candidate.weight
+    }
+}
+
+/// Rewards tracks of similar tempo: `weight` at the same BPM down to 0 at
+/// `max_diff` BPM apart and `-weight` at twice that. Tracks of unknown
+/// tempo score 0.
+#[derive(Debug, Clone, Copy)]
+pub struct TempoScorer {
+    pub max_diff: f32,
+    pub weight: i32,
+}
+
+impl Default for TempoScorer {
+    fn default() -> Self {
+        Self {
+            max_diff: 6.0,
+            weight: 10,
+        }
+    }
+}
+
+impl TransitionScorer for TempoScorer {
+    fn score(&self, candidate: &Candidate) -> i32 {
+        closeness(
+            candidate.from.bpm(),
+            candidate.to.bpm(),
+            self.max_diff,
+            self.weight,
+        )
+    }
+}
+
+/// Rewards small energy changes, like [`TempoScorer`] does for tempo.
+#[derive(Debug, Clone, Copy)]
+pub struct EnergyScorer {
+    pub max_diff: f32,
+    pub weight: i32,
+}
+
+impl Default for EnergyScorer {
+    fn default() -> Self {
+        Self {
+            max_diff: 0.2,
+            weight: 10,
+        }
+    }
+}
+
+impl TransitionScorer for EnergyScorer {
+    fn score(&self, candidate: &Candidate) -> i32 {
+        closeness(
+            candidate.from.energy(),
+            candidate.to.energy(),
+            self.max_diff,
+            self.weight,
+        )
+    }
+}
+
+/// Rewards tracks of similar loudness, `max_diff` being in dB.
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessScorer {
+    pub max_diff: f32,
+    pub weight: i32,
+}
+
+impl Default for LoudnessScorer {
+    fn default() -> Self {
+        Self {
+            max_diff: 3.0,
+            weight: 10,
+        }
+    }
+}
+
+impl TransitionScorer for LoudnessScorer {
+    fn score(&self, candidate: &Candidate) -> i32 {
+        closeness(
+            candidate.from.loudness(),
+            candidate.to.loudness(),
+            self.max_diff,
+            self.weight,
+        )
+    }
+}
+
+/// `same` for tracks sharing a genre, `different` for tracks that don't and
+/// 0 when either has none.
+#[derive(Debug, Clone, Copy)]
+pub struct GenreScorer {
+    pub same: i32,
+    pub different: i32,
+}
+
+impl Default for GenreScorer {
+    fn default() -> Self {
+        Self {
+            same: 10,
+            different: -10,
+        }
+    }
+}
+
+impl TransitionScorer for GenreScorer {
+    fn score(&self, candidate: &Candidate) -> i32 {
+        let (from, to) = (candidate.from.genres(), candidate.to.genres());
+        if from.is_empty() || to.is_empty() {
+            return 0;
+        }
+        let shared = from
+            .iter()
+            .any(|genre| to.iter().any(|other| normalize(genre) == normalize(other)));
+        if shared {
+            self.same
+        } else {
+            self.different
+        }
+    }
+}
+
+/// Looks up one of the built-in scorers listed in [`SCORER_NAMES`], with
+/// its default settings.
+pub fn scorer_by_name(name: &str) -> Option<Box<dyn TransitionScorer>> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "key" => Some(Box::new(KeyScorer)),
+        "tempo" => Some(Box::new(TempoScorer::default())),
+        "energy" => Some(Box::new(EnergyScorer::default())),
+        "loudness" => Some(Box::new(LoudnessScorer::default())),
+        "genre" => Some(Box::new(GenreScorer::default())),
+        _ => None,
+    }
+}
+
+/// The weighted sum of other scorers.
+#[derive(Default)]
+pub struct WeightedScorer<'s> {
+    parts: Vec<(f32, Box<dyn TransitionScorer + 's>)>,
+}
+
+impl<'s> WeightedScorer<'s> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(self, factor: f32, scorer: impl TransitionScorer + 's) -> Self {
+        self.with_boxed(factor, Box::new(scorer))
+    }
+
+    pub fn with_boxed(mut self, factor: f32, scorer: Box<dyn TransitionScorer + 's>) -> Self {
+        self.parts.push((factor, scorer));
+        self
+    }
+}
+
+impl TransitionScorer for WeightedScorer<'_> {
+    fn score(&self, candidate: &Candidate) -> i32 {
+        let total: f32 = self
+            .parts
+            .iter()
+            .map(|(factor, scorer)| factor * scorer.score(candidate) as f32)
+            .sum();
+        total.round() as i32
+    }
+}
+
+/// `weight` for equal values, falling linearly to `-weight` at twice
+/// `max_diff` apart; 0 when either value is unknown.
+fn closeness(a: Option<f32>, b: Option<f32>, max_diff: f32, weight: i32) -> i32 {
+    let (Some(a), Some(b)) = (a, b) else {
+        return 0;
+    };
+    if max_diff <= 0.0 {
+        return 0;
+    }
+    let share = (1.0 - (a - b).abs() / max_diff).max(-1.0);
+    (weight as f32 * share).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::{melodic_sort_with_scorer, MovementWeights};
+
+    #[test]
+    fn scorers_combine() {
+        let a = Track::from_pair("a", "8A")
+            .with_bpm(174.0)
+            .with_genres(["Neurofunk"]);
+        let b = Track::from_pair("b", "9A")
+            .with_bpm(171.0)
+            .with_genres(["neurofunk", "DnB"]);
+        let candidate = Candidate {
+            from: &a,
+            to: &b,
+            movement: Movement::EnergyBoost,
+            weight: 10,
+        };
+        assert_eq!(TempoScorer::default().score(&candidate), 5);
+        assert_eq!(GenreScorer::default().score(&candidate), 10);
+        assert_eq!(LoudnessScorer::default().score(&candidate), 0);
+
+        let bonus = |candidate: &Candidate| candidate.movement.name().len() as i32;
+        let scorer = WeightedScorer::new()
+            .with(1.0, KeyScorer)
+            .with(2.0, TempoScorer::default())
+            .with(0.5, bonus);
+        assert_eq!(scorer.score(&candidate), 10 + 10 + 6);
+    }
+
+    #[test]
+    fn melodic_sort_takes_any_scorer() {
+        // by key alone 8A -> 9A -> 10A, but b is far off in tempo
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_bpm(174.0),
+            Track::from_pair("b", "9A").with_bpm(140.0),
+            Track::from_pair("c", "10A").with_bpm(174.0),
+        ];
+        let weights = MovementWeights::default();
+        let scorer = WeightedScorer::new()
+            .with(1.0, KeyScorer)
+            .with(1.0, TempoScorer::default());
+        let sorted = melodic_sort_with_scorer(&tracks, &weights, &scorer, 100);
+        let names: Vec<&str> = sorted.iter().map(Track::name).collect();
+        assert_eq!(names, vec!["a", "c", "b"]);
+    }
+}
@@ -0,0 +1,578 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::algorithm::{build_pairs, Movement};
+use crate::rules::TransitionRules;
+use crate::scorer::Candidate;
+use crate::solver::{
+    KeylessHandling, KeylessPolicy, KeylessTrack, SortRequest, SortResult, Transition,
+};
+use crate::tempo::TempoShift;
+use crate::types::artist::normalize;
+use crate::types::key::Key;
+
+/// A way of playing a track: the track itself plus the key it is played in.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Node {
+    pub(crate) track: usize,
+    /// semitones the track is pitch-shifted by
+    pub(crate) shift: i8,
+    pub(crate) key: Option<Key>,
+    /// key of the intro, what the previous track mixes into
+    pub(crate) intro: Option<Key>,
+    /// key of the outro, what the next track mixes out of
+    pub(crate) outro: Option<Key>,
+}
+
+/// A transition between two nodes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Pair {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) movement: Movement,
+    /// weight of the movement minus the transposition penalty of `end`
+    pub(crate) weight: i32,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ScoredList {
+    /// node indices in play order
+    pub(crate) list: Vec<usize>,
+    pub(crate) score: i32,
+}
+
+/// Extra score steering a search, e.g. towards a previous plan. It is not
+/// part of the score of the result.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Steering {
+    /// added whenever a track is played
+    pub(crate) tracks: HashMap<usize, i32>,
+    /// added whenever one track follows another
+    pub(crate) transitions: HashMap<(usize, usize), i32>,
+}
+
+/// The transition graph of a [`SortRequest`] together with its constraints,
+/// shared by every [`crate::solver::Solver`] implementation.
+pub(crate) struct SearchSpace<'a> {
+    pub(crate) request: &'a SortRequest<'a>,
+    nodes: Vec<Node>,
+    /// normalized artists of every track, by track index
+    artists: Vec<Vec<String>>,
+    pairs_by_start: HashMap<usize, Vec<Pair>>,
+    pair_count: usize,
+    steering: Steering,
+    /// number of tracks a full set is expected to have, what the progress
+    /// of a [`crate::schedule::WeightSchedule`] is measured against
+    expected_len: usize,
+}
+
+impl<'a> SearchSpace<'a> {
+    pub(crate) fn new(request: &'a SortRequest<'a>) -> Self {
+        let nodes = build_nodes(request);
+        let pairs = match request.rules {
+            Some(rules) => build_pairs(&nodes, rules, request),
+            None => build_pairs(&nodes, &TransitionRules::camelot(request.weights), request),
+        };
+        let pair_count = pairs.len();
+        let mut pairs_by_start: HashMap<usize, Vec<Pair>> = HashMap::new();
+        for mut pair in pairs {
+            pair.weight = pair_weight(request, &nodes, &pair, pair.weight);
+            pairs_by_start.entry(pair.start).or_default().push(pair);
+        }
+        let playable = nodes
+            .iter()
+            .filter(|node| node.shift == 0)
+            .filter(|node| {
+                node.key.is_some() || matches!(request.keyless, KeylessPolicy::Wildcard { .. })
+            })
+            .count();
+        let expected_len = request
+            .constraints
+            .max_len
+            .map_or(playable, |max_len| max_len.min(playable));
+        let artists = request
+            .tracks
+            .iter()
+            .map(|track| track.artists().iter().map(|name| normalize(name)).collect())
+            .collect();
+        Self {
+            request,
+            nodes,
+            artists,
+            pairs_by_start,
+            pair_count,
+            steering: Steering::default(),
+            expected_len,
+        }
+    }
+
+    pub(crate) fn with_steering(mut self, steering: Steering) -> Self {
+        self.steering = steering;
+        self
+    }
+
+    pub(crate) fn pair_count(&self) -> usize {
+        self.pair_count
+    }
+
+    pub(crate) fn node(&self, node: usize) -> &Node {
+        &self.nodes[node]
+    }
+
+    /// The node playing `track` without a pitch shift.
+    pub(crate) fn track_node(&self, track: usize) -> Option<usize> {
+        self.node_of(track, 0)
+    }
+
+    /// The node playing `track` shifted by `shift` semitones.
+    pub(crate) fn node_of(&self, track: usize, shift: i8) -> Option<usize> {
+        self.nodes
+            .iter()
+            .position(|node| node.track == track && node.shift == shift)
+    }
+
+    /// Every node playing `track`, unshifted first.
+    pub(crate) fn track_nodes(&self, track: usize) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|&node| self.nodes[node].track == track)
+            .collect()
+    }
+
+    /// Number of distinct tracks reachable from `start` without passing
+    /// through any track in `excluded`, `start` itself not counted.
+    pub(crate) fn reachable_tracks(&self, start: usize, excluded: &[usize]) -> usize {
+        let start_track = self.nodes[start].track;
+        let mut visited = vec![false; self.nodes.len()];
+        let mut tracks = HashSet::new();
+        let mut queue = VecDeque::from([start]);
+        visited[start] = true;
+        while let Some(node) = queue.pop_front() {
+            for pair in self.successors(node) {
+                let track = self.nodes[pair.end].track;
+                if visited[pair.end] || track == start_track || excluded.contains(&track) {
+                    continue;
+                }
+                visited[pair.end] = true;
+                tracks.insert(track);
+                queue.push_back(pair.end);
+            }
+        }
+        tracks.len()
+    }
+
+    /// Nodes a list may open with, honouring `Constraints::first`.
+    pub(crate) fn opening_nodes(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|&node| self.may_open(node))
+            .collect()
+    }
+
+    fn may_open(&self, node: usize) -> bool {
+        self.request
+            .constraints
+            .first
+            .is_none_or(|first| self.nodes[node].track == first)
+    }
+
+    /// Pairs a list may open with, honouring `Constraints::first`.
+    pub(crate) fn opening_pairs(&self) -> Vec<Pair> {
+        let mut pairs: Vec<Pair> = self
+            .opening_nodes()
+            .into_iter()
+            .flat_map(|node| self.successors(node).iter().copied())
+            .collect();
+        pairs.sort_by_key(|pair| (pair.start, pair.end));
+        pairs
+    }
+
+    /// A list of just `node`, charged for its transposition.
+    pub(crate) fn start(&self, node: usize) -> ScoredList {
+        ScoredList {
+            list: vec![node],
+            score: -node_cost(self.request, &self.nodes[node]),
+        }
+    }
+
+    /// The two-track list formed by `pair`, if it is allowed.
+    pub(crate) fn open(&self, pair: &Pair) -> Option<ScoredList> {
+        let mut scored = self.start(pair.start);
+        let gain = self.step(&scored.list, pair)?;
+        scored.list.push(pair.end);
+        scored.score += gain;
+        Some(scored)
+    }
+
+    /// Score of playing `list` from the start, or `None` when any of its
+    /// steps is not allowed.
+    pub(crate) fn replay(&self, list: &[usize]) -> Option<i32> {
+        let (&first, rest) = list.split_first()?;
+        if !self.may_open(first) {
+            return None;
+        }
+        let mut score = self.start(first).score;
+        for position in 1..=rest.len() {
+            let pair = self.pair_between(list[position - 1], list[position])?;
+            score += self.step(&list[..position], pair)?;
+        }
+        Some(score + self.close(list)?)
+    }
+
+    /// Score the closing transition from the end of `list` back to its start
+    /// adds to a cyclic set; `Some(0)` when the set is not cyclic, `None`
+    /// when the list can't close.
+    pub(crate) fn close(&self, list: &[usize]) -> Option<i32> {
+        if !self.request.constraints.cyclic {
+            return Some(0);
+        }
+        let (&first, &last) = (list.first()?, list.last()?);
+        if list.len() < 2 {
+            return None;
+        }
+        let pair = self.pair_between(last, first)?;
+        if !self.wrap_allowed(list) {
+            return None;
+        }
+        // the opening node already paid for its transposition
+        let mut gain =
+            self.weight_at(list.len(), pair) + node_cost(self.request, &self.nodes[first]);
+        if let Some(history) = &self.request.history {
+            let recent = self.recent_moves(list, history.window);
+            gain += history.adjustment(&recent, pair.movement);
+        }
+        Some(gain)
+    }
+
+    /// Checks `Constraints::artist_gap` and `HistoryWeights::max_same_key_run`
+    /// across the loop of a cyclic set, from its end back into its start.
+    fn wrap_allowed(&self, list: &[usize]) -> bool {
+        let len = list.len();
+        let gap = self.request.constraints.artist_gap;
+        for first in 0..gap.min(len) {
+            let artists = &self.artists[self.nodes[list[first]].track];
+            let start = (len + first).saturating_sub(gap).max(first + 1);
+            let close = list[start..].iter().any(|&node| {
+                self.artists[self.nodes[node].track]
+                    .iter()
+                    .any(|name| artists.contains(name))
+            });
+            if close {
+                return false;
+            }
+        }
+        let Some(max_run) = self
+            .request
+            .history
+            .as_ref()
+            .and_then(|history| history.max_same_key_run)
+        else {
+            return true;
+        };
+        let key = |node: &&usize| self.nodes[**node].key;
+        let last = self.nodes[list[len - 1]].key;
+        let tail = list
+            .iter()
+            .rev()
+            .take_while(|node| key(node) == last)
+            .count();
+        if tail == len {
+            // one key all the way around
+            return false;
+        }
+        let head = list.iter().take_while(|node| key(node) == last).count();
+        tail + head <= max_run
+    }
+
+    /// Score of a list that was already played, where steps the request
+    /// does not allow simply add nothing.
+    pub(crate) fn replay_played(&self, list: &[usize]) -> i32 {
+        let Some(&first) = list.first() else {
+            return 0;
+        };
+        let mut score = self.start(first).score;
+        for position in 1..list.len() {
+            let gain = self
+                .pair_between(list[position - 1], list[position])
+                .and_then(|pair| self.step(&list[..position], pair));
+            score += gain.unwrap_or(0);
+        }
+        score
+    }
+
+    pub(crate) fn successors(&self, start: usize) -> &[Pair] {
+        self.pairs_by_start
+            .get(&start)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Score gained by appending `pair.end` to `list`, or `None` when the
+    /// step is not allowed.
+    pub(crate) fn step(&self, list: &[usize], pair: &Pair) -> Option<i32> {
+        if let Some(max_len) = self.request.constraints.max_len {
+            if list.len() >= max_len {
+                return None;
+            }
+        }
+        let track = self.nodes[pair.end].track;
+        if list.iter().any(|&node| self.nodes[node].track == track) {
+            return None;
+        }
+        if !self.artists_allowed(list, track) {
+            return None;
+        }
+        if let Some(max_duration) = self.request.constraints.max_duration {
+            let duration = |track: usize| self.request.tracks[track].duration().unwrap_or(0.0);
+            let total: f32 = list
+                .iter()
+                .map(|&node| duration(self.nodes[node].track))
+                .sum();
+            if total + duration(track) > max_duration {
+                return None;
+            }
+        }
+        let mut gain = self.weight_at(list.len(), pair);
+        let previous = self.nodes[pair.start].track;
+        gain += self.steering.tracks.get(&track).copied().unwrap_or(0);
+        gain += self
+            .steering
+            .transitions
+            .get(&(previous, track))
+            .copied()
+            .unwrap_or(0);
+        if let Some(history) = &self.request.history {
+            if let Some(max_run) = history.max_same_key_run {
+                let key = self.nodes[pair.end].key;
+                let run = list
+                    .iter()
+                    .rev()
+                    .take_while(|&&node| self.nodes[node].key == key)
+                    .count();
+                if run >= max_run {
+                    return None;
+                }
+            }
+            let recent = self.recent_moves(list, history.window);
+            gain += history.adjustment(&recent, pair.movement);
+        }
+        Some(gain)
+    }
+
+    /// Weight of `pair` played into position `position` of a set, which
+    /// only differs from `pair.weight` under a weight schedule.
+    fn weight_at(&self, position: usize, pair: &Pair) -> i32 {
+        let Some(schedule) = &self.request.schedule else {
+            return pair.weight;
+        };
+        if !Movement::BUILT_IN.contains(&pair.movement) {
+            return pair.weight;
+        }
+        let progress =
+            (position as f32 / self.expected_len.saturating_sub(1).max(1) as f32).min(1.0);
+        let weight = schedule.weights_at(progress).weight(pair.movement);
+        pair_weight(self.request, &self.nodes, pair, weight)
+    }
+
+    /// Checks `Constraints::artist_gap` and `Constraints::max_per_artist`
+    /// for appending `track` to `list`.
+    pub(crate) fn artists_allowed(&self, list: &[usize], track: usize) -> bool {
+        let constraints = &self.request.constraints;
+        let artists = &self.artists[track];
+        if artists.is_empty() {
+            return true;
+        }
+        let shares = |node: &usize| {
+            self.artists[self.nodes[*node].track]
+                .iter()
+                .any(|name| artists.contains(name))
+        };
+        if list.iter().rev().take(constraints.artist_gap).any(shares) {
+            return false;
+        }
+        let Some(max_per_artist) = constraints.max_per_artist else {
+            return true;
+        };
+        artists.iter().all(|name| {
+            let count = list
+                .iter()
+                .filter(|&&node| self.artists[self.nodes[node].track].contains(name))
+                .count();
+            count < max_per_artist
+        })
+    }
+
+    /// The movements of the last `window` transitions of `list`, oldest first.
+    fn recent_moves(&self, list: &[usize], window: usize) -> Vec<Movement> {
+        let skip = list.len().saturating_sub(window + 1);
+        list[skip..]
+            .windows(2)
+            .filter_map(|step| self.pair_between(step[0], step[1]))
+            .map(|pair| pair.movement)
+            .collect()
+    }
+
+    /// Whether the key confidences of tracks `from` and `to` are too low to
+    /// rely on a move between them.
+    pub(crate) fn is_risky(&self, from: usize, to: usize) -> bool {
+        let confidence = self.request.confidence.unwrap_or_default();
+        let tracks = self.request.tracks;
+        confidence.of(&tracks[from], &tracks[to]) < confidence.risky_below
+    }
+
+    pub(crate) fn pair_between(&self, start: usize, end: usize) -> Option<&Pair> {
+        self.successors(start).iter().find(|pair| pair.end == end)
+    }
+
+    pub(crate) fn to_result(&self, solver: &str, best: Option<ScoredList>) -> SortResult {
+        let mut result = match best {
+            Some(best) => self.ordered_result(solver, best),
+            None => SortResult::empty(solver),
+        };
+        self.place_keyless(&mut result);
+        result
+    }
+
+    pub(crate) fn ordered_result(&self, solver: &str, best: ScoredList) -> SortResult {
+        let mut transitions: Vec<Transition> = (1..best.list.len())
+            .filter_map(|position| {
+                let prefix = &best.list[..position];
+                let pair = self.pair_between(prefix[position - 1], best.list[position])?;
+                let (from, to) = (self.nodes[pair.start].track, self.nodes[pair.end].track);
+                Some(Transition {
+                    from,
+                    to,
+                    movement: pair.movement,
+                    weight: self.step(prefix, pair).unwrap_or(pair.weight),
+                    risky: self.is_risky(from, to),
+                })
+            })
+            .collect();
+        if self.request.constraints.cyclic {
+            let (first, last) = (best.list[0], best.list[best.list.len() - 1]);
+            if let (Some(pair), Some(weight)) =
+                (self.pair_between(last, first), self.close(&best.list))
+            {
+                let (from, to) = (self.nodes[last].track, self.nodes[first].track);
+                transitions.push(Transition {
+                    from,
+                    to,
+                    movement: pair.movement,
+                    weight,
+                    risky: self.is_risky(from, to),
+                });
+            }
+        }
+        SortResult {
+            solver: solver.to_string(),
+            order: best
+                .list
+                .iter()
+                .map(|&node| self.nodes[node].track)
+                .collect(),
+            shifts: best
+                .list
+                .iter()
+                .map(|&node| self.nodes[node].shift)
+                .collect(),
+            keys: best.list.iter().map(|&node| self.nodes[node].key).collect(),
+            score: best.score,
+            transitions,
+            keyless: Vec::new(),
+        }
+    }
+
+    /// Applies `SortRequest::keyless` to the tracks without a key and records
+    /// what happened to each of them.
+    fn place_keyless(&self, result: &mut SortResult) {
+        let keyless = self
+            .nodes
+            .iter()
+            .filter(|node| node.key.is_none())
+            .map(|node| node.track);
+        for track in keyless {
+            let handling = if result.order.contains(&track) {
+                KeylessHandling::Wildcard
+            } else if self.request.keyless == KeylessPolicy::Append {
+                result.order.push(track);
+                result.shifts.push(0);
+                result.keys.push(None);
+                KeylessHandling::Appended
+            } else {
+                KeylessHandling::Excluded
+            };
+            result.keyless.push(KeylessTrack { track, handling });
+        }
+    }
+}
+
+/// One node per track, plus one per allowed transposition when enabled.
+fn build_nodes(request: &SortRequest) -> Vec<Node> {
+    let max_shift = request
+        .transposition
+        .map(|transposition| transposition.max_semitones.min(6) as i8)
+        .unwrap_or(0);
+    let mut nodes = Vec::new();
+    for (track, value) in request.tracks.iter().enumerate() {
+        // played at the target tempo, every section moves by the same amount
+        let tempo_shift = request
+            .target_bpm
+            .and_then(|target_bpm| TempoShift::of(value, target_bpm))
+            .map_or(0, |tempo| tempo.key_shift);
+        let node = |shift: i8| Node {
+            track,
+            shift,
+            key: value.key().map(|key| key.transpose(tempo_shift + shift)),
+            intro: value
+                .intro_key()
+                .map(|key| key.transpose(tempo_shift + shift)),
+            outro: value
+                .outro_key()
+                .map(|key| key.transpose(tempo_shift + shift)),
+        };
+        nodes.push(node(0));
+        if value.key().is_none() {
+            continue;
+        }
+        for magnitude in 1..=max_shift {
+            for shift in [magnitude, -magnitude] {
+                // +6 and -6 land on the same key
+                if shift == -6 {
+                    continue;
+                }
+                nodes.push(node(shift));
+            }
+        }
+    }
+    nodes
+}
+
+/// `weight` of a movement from `pair.start` into `pair.end`, discounted for
+/// unsure keys and charged for the transposition of `pair.end`.
+fn pair_weight(request: &SortRequest, nodes: &[Node], pair: &Pair, weight: i32) -> i32 {
+    let mut weight = weight;
+    if let Some(scorer) = request.scorer {
+        let (start, end) = (nodes[pair.start].track, nodes[pair.end].track);
+        weight = scorer.score(&Candidate {
+            from: &request.tracks[start],
+            to: &request.tracks[end],
+            movement: pair.movement,
+            weight,
+        });
+    }
+    if let Some(confidence) = &request.confidence {
+        let (start, end) = (nodes[pair.start].track, nodes[pair.end].track);
+        let combined = confidence.of(&request.tracks[start], &request.tracks[end]);
+        weight = confidence.discount(weight, combined);
+    }
+    weight - node_cost(request, &nodes[pair.end])
+}
+
+fn node_cost(request: &SortRequest, node: &Node) -> i32 {
+    request
+        .transposition
+        .map(|transposition| transposition.penalty_per_semitone * node.shift.unsigned_abs() as i32)
+        .unwrap_or(0)
+}
+
+/// Whether a list of `len` tracks scoring `score` beats the current best.
+pub(crate) fn is_better(len: usize, score: i32, best_len: usize, best_score: i32) -> bool {
+    len > best_len || (len == best_len && score > best_score)
+}
@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+
+use loggit::debug;
+
+use crate::algorithm::Movement;
+use crate::learn::DEFAULT_SCALE;
+use crate::search::SearchSpace;
+use crate::solver::SortRequest;
+
+/// Smallest [`ShuffleOptions::scale`], and scale of
+/// [`crate::learn::MovementCounts::fit_of`]; smaller and invalid ones are
+/// raised to it.
+pub const MIN_SCALE: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShuffleOptions {
+    /// the same seed always plays the same sequence
+    pub seed: u64,
+    /// number of most recent tracks that are not played again; shrinks when
+    /// the library is too small for it
+    pub no_repeat: usize,
+    /// a movement is picked with a probability proportional to
+    /// `exp(weight / scale)`, so a smaller scale favours the best moves more;
+    /// at [`MIN_SCALE`] and below, only the best ones are played
+    pub scale: f32,
+    /// track to open with, a random one when `None`
+    pub start: Option<usize>,
+}
+
+impl Default for ShuffleOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            no_repeat: 10,
+            scale: DEFAULT_SCALE,
+            start: None,
+        }
+    }
+}
+
+/// One track of a [`HarmonicShuffle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShuffleStep {
+    /// index into [`SortRequest::tracks`]
+    pub track: usize,
+    /// semitones the track is pitch-shifted by
+    pub shift: i8,
+    /// how the previous track mixes into this one, `None` for the first
+    /// track and after a dead end
+    pub movement: Option<Movement>,
+}
+
+/// An endless sequence of tracks of `request.tracks`, each picked at random
+/// among the ones the previous track mixes into, see [`harmonic_shuffle`].
+pub struct HarmonicShuffle<'a> {
+    space: SearchSpace<'a>,
+    options: ShuffleOptions,
+    rng: SplitMix64,
+    /// nodes played most recently, oldest first
+    recent: VecDeque<usize>,
+    /// unshifted nodes of the tracks with a key
+    playable: Vec<usize>,
+    jumps: usize,
+}
+
+/// Shuffles `request.tracks` harmonically and forever: every next track is
+/// drawn among the unshifted or transposed tracks the current one mixes
+/// into, weighted by the request's scoring and its artist constraints, but
+/// never one of the last `options.no_repeat` tracks. At a dead end it jumps
+/// to a random track that was not played recently.
+pub fn harmonic_shuffle<'a>(
+    request: &'a SortRequest<'a>,
+    options: ShuffleOptions,
+) -> HarmonicShuffle<'a> {
+    let space = SearchSpace::new(request);
+    let playable = (0..request.tracks.len())
+        .filter_map(|track| space.track_node(track))
+        .filter(|&node| space.node(node).key.is_some())
+        .collect();
+    HarmonicShuffle {
+        space,
+        options,
+        rng: SplitMix64(options.seed),
+        recent: VecDeque::new(),
+        playable,
+        jumps: 0,
+    }
+}
+
+impl HarmonicShuffle<'_> {
+    /// Number of dead ends the shuffle jumped out of so far.
+    pub fn jumps(&self) -> usize {
+        self.jumps
+    }
+
+    /// The recent window, never so large that no track is left to play.
+    fn window(&self) -> usize {
+        self.options
+            .no_repeat
+            .min(self.playable.len().saturating_sub(1))
+    }
+
+    fn played_recently(&self, track: usize) -> bool {
+        let skip = self.recent.len().saturating_sub(self.window());
+        self.recent
+            .iter()
+            .skip(skip)
+            .any(|&node| self.space.node(node).track == track)
+    }
+
+    /// A harmonic successor of `current`, drawn by weight.
+    fn follow(&mut self, current: usize) -> Option<(usize, Movement)> {
+        let recent: Vec<usize> = self.recent.iter().copied().collect();
+        let candidates: Vec<(usize, Movement, i32)> = self
+            .space
+            .successors(current)
+            .iter()
+            .filter(|pair| {
+                let track = self.space.node(pair.end).track;
+                !self.played_recently(track) && self.space.artists_allowed(&recent, track)
+            })
+            .map(|pair| (pair.end, pair.movement, pair.weight))
+            .collect();
+        let best = candidates.iter().map(|&(_, _, weight)| weight).max()?;
+        // `max` also replaces a NaN scale
+        let scale = self.options.scale.max(MIN_SCALE) as f64;
+        // relative to the best weight, so large weights can't overflow
+        let odds: Vec<f64> = candidates
+            .iter()
+            .map(|&(_, _, weight)| ((weight - best) as f64 / scale).exp())
+            .collect();
+        let mut pick = self.rng.next_f64() * odds.iter().sum::<f64>();
+        for (&(node, movement, _), odds) in candidates.iter().zip(&odds) {
+            if pick < *odds {
+                return Some((node, movement));
+            }
+            pick -= odds;
+        }
+        candidates
+            .last()
+            .map(|&(node, movement, _)| (node, movement))
+    }
+
+    /// A random playable track that was not played recently.
+    fn jump(&mut self) -> Option<usize> {
+        let fresh: Vec<usize> = self
+            .playable
+            .iter()
+            .copied()
+            .filter(|&node| !self.played_recently(self.space.node(node).track))
+            .collect();
+        if fresh.is_empty() {
+            return None;
+        }
+        Some(fresh[self.rng.below(fresh.len())])
+    }
+}
+
+impl Iterator for HarmonicShuffle<'_> {
+    type Item = ShuffleStep;
+
+    fn next(&mut self) -> Option<ShuffleStep> {
+        let (node, movement) = match self.recent.back() {
+            Some(&current) => match self.follow(current) {
+                Some((node, movement)) => (node, Some(movement)),
+                None => {
+                    self.jumps += 1;
+                    debug!("harmonic_shuffle: dead end after node {}", current);
+                    (self.jump()?, None)
+                }
+            },
+            None => {
+                let start = self
+                    .options
+                    .start
+                    .and_then(|track| self.space.track_node(track));
+                (start.or_else(|| self.jump())?, None)
+            }
+        };
+        self.recent.push_back(node);
+        while self.recent.len() > self.options.no_repeat.max(1) {
+            self.recent.pop_front();
+        }
+        let node = self.space.node(node);
+        Some(ShuffleStep {
+            track: node.track,
+            shift: node.shift,
+            movement,
+        })
+    }
+}
+
+/// SplitMix64, small and good enough to shuffle a playlist.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `0..n`, `n` being positive.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::MovementWeights;
+    use crate::types::track::Track;
+
+    #[test]
+    fn plays_forever_without_repeating_recent_tracks() {
+        let tracks: Vec<Track> = ["8A", "9A", "10A", "8B", "9B", "10B", "9A", "8A"]
+            .iter()
+            .map(|key| Track::from_pair(key, key))
+            .collect();
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+        let options = ShuffleOptions {
+            seed: 7,
+            no_repeat: 4,
+            start: Some(0),
+            ..ShuffleOptions::default()
+        };
+
+        let played: Vec<ShuffleStep> = harmonic_shuffle(&request, options).take(200).collect();
+        assert_eq!(played.len(), 200);
+        assert_eq!(played[0].track, 0);
+        for window in played.windows(5) {
+            let last = window[4].track;
+            assert!(window[..4].iter().all(|step| step.track != last));
+        }
+        let again: Vec<ShuffleStep> = harmonic_shuffle(&request, options).take(200).collect();
+        assert_eq!(played, again);
+        let other_seed = ShuffleOptions { seed: 8, ..options };
+        let other: Vec<ShuffleStep> = harmonic_shuffle(&request, other_seed).take(200).collect();
+        assert_ne!(played, other);
+    }
+
+    #[test]
+    fn jumps_out_of_dead_ends() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "3B"),
+            Track::new(None, "no key", "", None),
+        ];
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+
+        let mut shuffle = harmonic_shuffle(&request, ShuffleOptions::default());
+        let played: Vec<ShuffleStep> = shuffle.by_ref().take(6).collect();
+        let order: Vec<usize> = played.iter().map(|step| step.track).collect();
+        assert!(order == [0, 1, 0, 1, 0, 1] || order == [1, 0, 1, 0, 1, 0]);
+        assert!(played.iter().all(|step| step.movement.is_none()));
+        assert_eq!(shuffle.jumps(), 5);
+    }
+
+    #[test]
+    fn small_or_invalid_scales_play_the_best_move() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "9A"),
+            Track::from_pair("c", "8B"),
+        ];
+        let weights = MovementWeights {
+            energy_boost: 30,
+            ..MovementWeights::default()
+        };
+        let request = SortRequest::new(&tracks, &weights);
+        for scale in [0.0, -1.0, f32::NAN] {
+            for seed in 0..10 {
+                let options = ShuffleOptions {
+                    seed,
+                    scale,
+                    start: Some(0),
+                    ..ShuffleOptions::default()
+                };
+                let step = harmonic_shuffle(&request, options).nth(1).unwrap();
+                assert_eq!(step.track, 1);
+                assert_eq!(step.movement, Some(Movement::EnergyBoost));
+            }
+        }
+    }
+}
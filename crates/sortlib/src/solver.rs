@@ -0,0 +1,765 @@
+use loggit::{debug, info};
+
+use crate::algorithm::{beam_search, ConfidenceWeights, HistoryWeights, Movement, MovementWeights};
+use crate::rules::TransitionRules;
+use crate::schedule::WeightSchedule;
+use crate::scorer::TransitionScorer;
+use crate::search::{is_better, ScoredList, SearchSpace};
+use crate::types::key::Key;
+use crate::types::track::Track;
+
+/// Names accepted by [`solver_by_name`].
+pub const SOLVER_NAMES: [&str; 3] = ["beam", "greedy", "exhaustive"];
+
+/// Everything a [`Solver`] needs to order a set of tracks.
+#[derive(Debug, Clone)]
+pub struct SortRequest<'a> {
+    /// tracks to order, referenced by index in the result
+    pub tracks: &'a [Track],
+    /// weights used to score every transition
+    pub weights: &'a MovementWeights,
+    /// weights that change over the set, replacing `weights` for the
+    /// built-in movements; off when `None`
+    pub schedule: Option<WeightSchedule>,
+    /// scores every transition in place of the movement weight, see
+    /// [`crate::scorer`]; off when `None`
+    pub scorer: Option<&'a dyn TransitionScorer>,
+    /// transition rules, the Camelot wheel rules when `None`
+    pub rules: Option<&'a TransitionRules>,
+    /// scoring based on the previous moves, off when `None`
+    pub history: Option<HistoryWeights>,
+    /// discount for transitions between unsure keys, off when `None`; risky
+    /// transitions are flagged either way
+    pub confidence: Option<ConfidenceWeights>,
+    /// pitch shifting tracks are allowed to use, none when `None`
+    pub transposition: Option<Transposition>,
+    /// tempo of the set when playing without key-lock; tracks are matched by
+    /// the key they sound in at that tempo, see [`crate::tempo`]
+    pub target_bpm: Option<f32>,
+    /// what happens to tracks without a key
+    pub keyless: KeylessPolicy,
+    /// restrictions every produced order must satisfy
+    pub constraints: Constraints,
+    /// how much work a solver may spend
+    pub budget: Budget,
+}
+
+impl<'a> SortRequest<'a> {
+    pub fn new(tracks: &'a [Track], weights: &'a MovementWeights) -> Self {
+        Self {
+            tracks,
+            weights,
+            schedule: None,
+            scorer: None,
+            rules: None,
+            history: None,
+            confidence: None,
+            transposition: None,
+            target_bpm: None,
+            keyless: KeylessPolicy::default(),
+            constraints: Constraints::default(),
+            budget: Budget::default(),
+        }
+    }
+}
+
+/// What solvers do with tracks that have no key.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum KeylessPolicy {
+    /// leave them out of the set
+    #[default]
+    Exclude,
+    /// play them after the sorted set, in their original order
+    Append,
+    /// let them sit between any two keyed tracks, with a
+    /// [`Movement::Wildcard`] on both sides
+    Wildcard {
+        /// score of a move from or to a keyless track
+        weight: i32,
+        /// largest tempo difference to a neighbour in BPM, tempo is not
+        /// checked when `None`
+        max_bpm_diff: Option<f32>,
+        /// largest energy difference to a neighbour, energy is not checked
+        /// when `None`
+        max_energy_diff: Option<f32>,
+    },
+}
+
+/// How a track without a key ended up being handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeylessHandling {
+    Excluded,
+    Appended,
+    /// placed inside the set as a wildcard
+    Wildcard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeylessTrack {
+    /// index into [`SortRequest::tracks`]
+    pub track: usize,
+    pub handling: KeylessHandling,
+}
+
+/// Lets solvers play a track up to `max_semitones` up or down (with key-lock)
+/// when that makes it mix with its neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transposition {
+    /// largest shift in semitones, at most 6
+    pub max_semitones: u8,
+    /// score subtracted per semitone a track is shifted by
+    pub penalty_per_semitone: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Constraints {
+    /// index of the track the set has to open with
+    pub first: Option<usize>,
+    /// maximum number of tracks in the set
+    pub max_len: Option<usize>,
+    /// least number of other tracks between two tracks sharing an artist
+    pub artist_gap: usize,
+    /// most tracks a single artist may appear on, featured or remixing included
+    pub max_per_artist: Option<usize>,
+    /// longest total playing time in seconds; tracks of unknown length are
+    /// not counted
+    pub max_duration: Option<f32>,
+    /// the last track has to mix back into the first one; the closing
+    /// transition is scored like any other
+    pub cyclic: bool,
+    /// movements never used, whatever their weight
+    pub forbidden_movements: Vec<Movement>,
+    /// `(from, to)` track indices that are never mixed in that direction
+    pub forbidden_pairs: Vec<(usize, usize)>,
+    /// largest tempo change in BPM between consecutive tracks whose tempo
+    /// is known
+    pub max_bpm_step: Option<f32>,
+    /// largest energy change between consecutive tracks whose energy is
+    /// known
+    pub max_energy_step: Option<f32>,
+}
+
+impl Constraints {
+    /// Whether mixing track `from` into track `to` with `movement` is ruled
+    /// out.
+    pub fn forbids(&self, from: usize, to: usize, movement: Movement) -> bool {
+        self.forbidden_movements.contains(&movement) || self.forbidden_pairs.contains(&(from, to))
+    }
+
+    /// Whether `from` and `to` are too far apart in tempo or energy to be
+    /// mixed into each other.
+    pub fn too_far(&self, from: &Track, to: &Track) -> bool {
+        let exceeds = |max_step: Option<f32>, a: Option<f32>, b: Option<f32>| match (max_step, a, b)
+        {
+            (Some(max_step), Some(a), Some(b)) => (a - b).abs() > max_step,
+            _ => false,
+        };
+        exceeds(self.max_bpm_step, from.bpm(), to.bpm())
+            || exceeds(self.max_energy_step, from.energy(), to.energy())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    /// number of partial lists the beam search keeps per layer
+    pub beam_width: usize,
+    /// number of search nodes the exhaustive solver may visit
+    pub max_expansions: usize,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            beam_width: 1000,
+            max_expansions: 1_000_000,
+        }
+    }
+}
+
+/// A single step of a sorted set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub from: usize,
+    pub to: usize,
+    pub movement: Movement,
+    /// score the step added to the set, including penalties and bonuses
+    pub weight: i32,
+    /// the key confidences of both tracks are too low to rely on the move
+    pub risky: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SortResult {
+    /// name of the solver that produced the result
+    pub solver: String,
+    /// indices into [`SortRequest::tracks`] in play order
+    pub order: Vec<usize>,
+    /// semitones each track of `order` is deliberately pitch-shifted by
+    pub shifts: Vec<i8>,
+    /// key each track of `order` is heard in, after tempo and pitch shifts
+    pub keys: Vec<Option<Key>>,
+    /// total score of the order
+    pub score: i32,
+    /// transitions between consecutive tracks of `order`
+    pub transitions: Vec<Transition>,
+    /// every track without a key and how it was handled
+    pub keyless: Vec<KeylessTrack>,
+}
+
+impl SortResult {
+    pub(crate) fn empty(solver: &str) -> Self {
+        Self {
+            solver: solver.to_string(),
+            order: Vec::new(),
+            shifts: Vec::new(),
+            keys: Vec::new(),
+            score: 0,
+            transitions: Vec::new(),
+            keyless: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Clones the ordered tracks out of the slice the request was built from.
+    pub fn tracks(&self, tracks: &[Track]) -> Vec<Track> {
+        self.order
+            .iter()
+            .map(|&index| tracks[index].clone())
+            .collect()
+    }
+
+    /// Describes every transition, e.g. `8A -> 9A EnergyBoost (+10)`, naming
+    /// custom movements after their rule in `rules` and marking risky ones
+    /// with a trailing `risky`. Shifted tracks show their played key, e.g.
+    /// `8A -> 4A(+1) EnergyBoost (+4)`.
+    pub fn explain(&self, rules: Option<&TransitionRules>) -> Vec<String> {
+        // steps the rules don't allow, e.g. in a played prefix, have no
+        // transition, and a cyclic set closes back to its start, so positions
+        // are looked up
+        let key = |track: usize| {
+            let position = self
+                .order
+                .iter()
+                .position(|&other| other == track)
+                .unwrap_or_default();
+            let played = self.keys[position]
+                .map(|key| key.to_string())
+                .unwrap_or_else(|| "?".to_string());
+            match self.shifts[position] {
+                0 => played,
+                shift => format!("{played}({shift:+})"),
+            }
+        };
+        self.transitions
+            .iter()
+            .map(|transition| {
+                let name = match rules {
+                    Some(rules) => rules.name_of(transition.movement).to_string(),
+                    None => transition.movement.to_string(),
+                };
+                let risky = if transition.risky { " risky" } else { "" };
+                format!(
+                    "{} -> {} {} ({:+}){}",
+                    key(transition.from),
+                    key(transition.to),
+                    name,
+                    transition.weight,
+                    risky
+                )
+            })
+            .collect()
+    }
+}
+
+/// A strategy for turning a [`SortRequest`] into an ordered set.
+///
+/// Every solver prefers longer sets first and higher scores second.
+pub trait Solver {
+    fn name(&self) -> &'static str;
+    fn solve(&self, request: &SortRequest) -> SortResult;
+}
+
+/// Looks up one of the built-in solvers listed in [`SOLVER_NAMES`].
+pub fn solver_by_name(name: &str) -> Option<Box<dyn Solver>> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "beam" => Some(Box::new(BeamSolver)),
+        "greedy" => Some(Box::new(GreedySolver)),
+        "exhaustive" => Some(Box::new(ExhaustiveSolver)),
+        _ => None,
+    }
+}
+
+/// Layered beam search keeping the `budget.beam_width` best partial lists.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BeamSolver;
+
+impl Solver for BeamSolver {
+    fn name(&self) -> &'static str {
+        "beam"
+    }
+
+    fn solve(&self, request: &SortRequest) -> SortResult {
+        let space = SearchSpace::new(request);
+        let best = beam_search(&space, request.budget.beam_width);
+        space.to_result(self.name(), best)
+    }
+}
+
+/// Nearest-neighbour walk from every possible opening track, always taking
+/// the best scoring unplayed successor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedySolver;
+
+impl Solver for GreedySolver {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn solve(&self, request: &SortRequest) -> SortResult {
+        let space = SearchSpace::new(request);
+
+        let mut best: Option<ScoredList> = None;
+        for start in space.opening_nodes() {
+            let mut scored = space.start(start);
+            loop {
+                let &end = scored.list.last().unwrap();
+                let next = space
+                    .successors(end)
+                    .iter()
+                    .filter_map(|pair| space.step(&scored.list, pair).map(|gain| (pair.end, gain)))
+                    .max_by_key(|&(index, gain)| (gain, std::cmp::Reverse(index)));
+                let Some((index, gain)) = next else { break };
+                scored.list.push(index);
+                scored.score += gain;
+                // a cyclic walk may only close before its end
+                let Some(closing) = space.close(&scored.list) else {
+                    continue;
+                };
+                let score = scored.score + closing;
+                let replace = best.as_ref().is_none_or(|current| {
+                    is_better(scored.list.len(), score, current.list.len(), current.score)
+                });
+                if replace {
+                    best = Some(ScoredList {
+                        list: scored.list.clone(),
+                        score,
+                    });
+                }
+            }
+            debug!(
+                "greedy: start={} len={} score={}",
+                start,
+                scored.list.len(),
+                scored.score
+            );
+        }
+
+        space.to_result(self.name(), best)
+    }
+}
+
+/// Depth-first search over every order, exact as long as it stays within
+/// `budget.max_expansions`; past that it returns the best order found so far.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExhaustiveSolver;
+
+impl Solver for ExhaustiveSolver {
+    fn name(&self) -> &'static str {
+        "exhaustive"
+    }
+
+    fn solve(&self, request: &SortRequest) -> SortResult {
+        let space = SearchSpace::new(request);
+        let mut state = Exhaustive {
+            space: &space,
+            expansions: 0,
+            max_expansions: request.budget.max_expansions,
+            best: None,
+        };
+
+        for pair in space.opening_pairs() {
+            let Some(mut scored) = space.open(&pair) else {
+                continue;
+            };
+            state.visit(&mut scored.list, scored.score);
+        }
+        info!(
+            "exhaustive: expansions={} complete={}",
+            state.expansions,
+            state.expansions < state.max_expansions
+        );
+
+        let best = state.best;
+        space.to_result(self.name(), best)
+    }
+}
+
+struct Exhaustive<'s, 'a> {
+    space: &'s SearchSpace<'a>,
+    expansions: usize,
+    max_expansions: usize,
+    best: Option<ScoredList>,
+}
+
+impl Exhaustive<'_, '_> {
+    fn visit(&mut self, list: &mut Vec<usize>, score: i32) {
+        if let Some(closing) = self.space.close(list) {
+            let closed = score + closing;
+            let replace = self
+                .best
+                .as_ref()
+                .is_none_or(|best| is_better(list.len(), closed, best.list.len(), best.score));
+            if replace {
+                self.best = Some(ScoredList {
+                    list: list.clone(),
+                    score: closed,
+                });
+            }
+        }
+        if self.expansions >= self.max_expansions {
+            return;
+        }
+        self.expansions += 1;
+
+        let &end = list.last().unwrap();
+        let space = self.space;
+        for pair in space.successors(end) {
+            let Some(gain) = space.step(list, pair) else {
+                continue;
+            };
+            list.push(pair.end);
+            self.visit(list, score + gain);
+            list.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracks() -> Vec<Track> {
+        vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "2B"),
+            Track::from_pair("c", "9A"),
+            Track::from_pair("d", "8B"),
+            Track::from_pair("e", "8A"),
+        ]
+    }
+
+    #[test]
+    fn solvers_agree_on_small_sets() {
+        let tracks = tracks();
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+
+        let exhaustive = ExhaustiveSolver.solve(&request);
+        assert_eq!(exhaustive.len(), 4);
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.solver, name);
+            assert_eq!(result.len(), 4);
+            assert!(result.score <= exhaustive.score);
+            assert_eq!(result.transitions.len(), 3);
+            assert_eq!(
+                result.score,
+                result.transitions.iter().map(|t| t.weight).sum::<i32>()
+            );
+        }
+    }
+
+    #[test]
+    fn constraints_pin_first_track_and_length() {
+        let tracks = tracks();
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.first = Some(3);
+        request.constraints.max_len = Some(2);
+
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.order.len(), 2);
+            assert_eq!(result.order[0], 3);
+        }
+        assert!(solver_by_name("annealing").is_none());
+    }
+
+    #[test]
+    fn cyclic_sets_mix_back_into_their_start() {
+        // 12A only mixes with 10A, so it can't be part of a loop
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "9A"),
+            Track::from_pair("c", "10A"),
+            Track::from_pair("d", "12A"),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        assert_eq!(BeamSolver.solve(&request).len(), 4);
+        request.constraints.cyclic = true;
+
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.len(), 3, "{name}");
+            assert_eq!(result.transitions.len(), 3);
+            let closing = result.transitions.last().unwrap();
+            assert_eq!(closing.from, result.order[2]);
+            assert_eq!(closing.to, result.order[0]);
+            assert_eq!(result.score, 20);
+            assert_eq!(
+                result.score,
+                result.transitions.iter().map(|t| t.weight).sum::<i32>()
+            );
+        }
+    }
+
+    #[test]
+    fn cyclic_sets_keep_the_artist_gap_across_the_loop() {
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_artists(["Burr Oak"]),
+            Track::from_pair("b", "8A").with_artists(["Gancher"]),
+            Track::from_pair("c", "8A").with_artists(["Burr Oak"]),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.artist_gap = 1;
+        assert_eq!(BeamSolver.solve(&request).order[1], 1);
+        request.constraints.cyclic = true;
+
+        // looping a-b-c would play c straight into a
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.len(), 2, "{name}");
+            assert!(result.order.contains(&1));
+        }
+    }
+
+    #[test]
+    fn forbidden_movements_and_pairs_are_never_used() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "9A"),
+            Track::from_pair("c", "10A"),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.forbidden_movements = vec![Movement::EnergyBoost];
+        request.constraints.forbidden_pairs = vec![(2, 1)];
+
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.order, vec![1, 0, 2], "{name}");
+            assert!(result
+                .transitions
+                .iter()
+                .all(|t| t.movement != Movement::EnergyBoost));
+        }
+    }
+
+    #[test]
+    fn unsure_keys_are_discounted_and_flagged() {
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_key_confidence(0.5),
+            Track::from_pair("b", "9A").with_key_confidence(0.5),
+            Track::from_pair("c", "10A"),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.first = Some(0);
+        request.confidence = Some(ConfidenceWeights::default());
+
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.order, vec![0, 1, 2]);
+        let weights: Vec<i32> = result.transitions.iter().map(|t| t.weight).collect();
+        assert_eq!(weights, vec![3, 5]);
+        assert!(result.transitions[0].risky);
+        assert!(!result.transitions[1].risky);
+        assert_eq!(result.explain(None)[0], "8A -> 9A EnergyBoost (+3) risky");
+    }
+
+    #[test]
+    fn pairs_mix_the_outro_into_the_next_intro() {
+        let key = |camelot: &str| Key::from_camelot(camelot).unwrap();
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_outro_key(key("3A")),
+            Track::from_pair("b", "1B").with_intro_key(key("3A")),
+        ];
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.order, vec![0, 1], "{name}");
+            assert_eq!(result.transitions[0].movement, Movement::PerfectMatch);
+        }
+        // b's outro falls back to 1B, which doesn't mix into a's 8A intro
+        let reversed = vec![tracks[1].clone(), tracks[0].clone()];
+        assert!(!crate::evaluate::evaluate_order(&reversed, &weights).is_valid());
+    }
+
+    #[test]
+    fn keyless_tracks_follow_the_policy() {
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_bpm(174.0).with_energy(0.5),
+            Track::new(None, "unknown", "", None)
+                .with_bpm(172.0)
+                .with_energy(0.9),
+            Track::from_pair("b", "9A").with_energy(0.8),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        let handling = |result: &SortResult| {
+            assert_eq!(result.keyless.len(), 1);
+            assert_eq!(result.keyless[0].track, 1);
+            result.keyless[0].handling
+        };
+
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.order, vec![0, 2]);
+        assert_eq!(handling(&result), KeylessHandling::Excluded);
+
+        request.keyless = KeylessPolicy::Append;
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.order, vec![0, 2, 1]);
+        assert_eq!(result.keys[2], None);
+        assert_eq!(handling(&result), KeylessHandling::Appended);
+
+        request.keyless = KeylessPolicy::Wildcard {
+            weight: 2,
+            max_bpm_diff: None,
+            max_energy_diff: None,
+        };
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result.score, weights.energy_boost + 2);
+        assert!(result
+            .transitions
+            .iter()
+            .any(|t| t.movement == Movement::Wildcard));
+        assert_eq!(handling(&result), KeylessHandling::Wildcard);
+
+        // only 8A has a tempo close enough to sit next to the keyless track
+        request.keyless = KeylessPolicy::Wildcard {
+            weight: 2,
+            max_bpm_diff: Some(4.0),
+            max_energy_diff: None,
+        };
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result.order[1], 0);
+
+        // and only 9A an energy close enough
+        request.keyless = KeylessPolicy::Wildcard {
+            weight: 2,
+            max_bpm_diff: None,
+            max_energy_diff: Some(0.2),
+        };
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result.order[1], 2);
+    }
+
+    #[test]
+    fn transposition_links_incompatible_tracks() {
+        // 8A and 4A only mix once one of them is shifted by a semitone
+        let tracks = vec![Track::from_pair("a", "8A"), Track::from_pair("b", "4A")];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        assert!(BeamSolver.solve(&request).is_empty());
+
+        request.transposition = Some(Transposition {
+            max_semitones: 1,
+            penalty_per_semitone: 5,
+        });
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.len(), 2);
+            assert_eq!(result.shifts.iter().map(|s| s.abs()).sum::<i8>(), 1);
+            assert_eq!(result.score, weights.energy_boost - 5);
+            assert_eq!(result.transitions[0].movement, Movement::EnergyBoost);
+        }
+    }
+
+    #[test]
+    fn target_bpm_sorts_by_effective_keys() {
+        // 164 BPM sped up to 174 BPM rises a semitone, from 8A to 3A
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_bpm(164.0),
+            Track::from_pair("b", "3A").with_bpm(174.0),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        assert_eq!(BeamSolver.solve(&request).score, weights.energy_raise);
+
+        request.target_bpm = Some(174.0);
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.score, weights.perfect_match);
+        assert_eq!(result.keys, vec![Key::from_camelot("3A").ok(); 2]);
+    }
+
+    #[test]
+    fn history_limits_same_key_runs() {
+        let tracks = vec![
+            Track::from_pair("a", "8A"),
+            Track::from_pair("b", "8A"),
+            Track::from_pair("c", "8A"),
+            Track::from_pair("d", "9A"),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.history = Some(HistoryWeights {
+            window: 2,
+            repeat_penalty: 20,
+            max_same_key_run: Some(2),
+            variety_bonus: 1,
+        });
+
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            assert_eq!(result.len(), 4);
+            assert!(result
+                .keys
+                .windows(3)
+                .all(|run| run[0] != run[2] || run[0] != run[1]));
+            assert_eq!(
+                result.score,
+                result.transitions.iter().map(|t| t.weight).sum::<i32>()
+            );
+        }
+    }
+
+    #[test]
+    fn artist_constraints_are_enforced_in_the_search() {
+        let tracks = vec![
+            Track::from_pair("a", "8A").with_artists(["Burr Oak"]),
+            Track::from_pair("b", "8A").with_credit("Finalfix", "Savages (Burr Oak Remix)"),
+            Track::from_pair("c", "8A").with_artists(["Magnetude"]),
+            Track::from_pair("d", "8A").with_artists(["burr oak"]),
+        ];
+        let weights = MovementWeights::default();
+        let mut request = SortRequest::new(&tracks, &weights);
+        request.constraints.artist_gap = 1;
+
+        for name in SOLVER_NAMES {
+            let result = solver_by_name(name).unwrap().solve(&request);
+            let ordered = result.tracks(&tracks);
+            assert!(ordered
+                .windows(2)
+                .all(|pair| !pair[0].shares_artist(&pair[1])));
+        }
+
+        request.constraints.artist_gap = 0;
+        request.constraints.max_per_artist = Some(2);
+        let result = BeamSolver.solve(&request);
+        assert_eq!(result.len(), 3);
+        assert!(result.order.contains(&2));
+    }
+}
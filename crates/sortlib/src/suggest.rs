@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use loggit::debug;
+
+use crate::algorithm::Movement;
+use crate::search::SearchSpace;
+use crate::solver::SortRequest;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SuggestOptions {
+    /// number of suggestions returned
+    pub count: usize,
+    /// score added when every unplayed track stays reachable after the
+    /// candidate, scaled down with the share that does
+    pub reachability_weight: i32,
+}
+
+impl Default for SuggestOptions {
+    fn default() -> Self {
+        Self {
+            count: 5,
+            reachability_weight: 20,
+        }
+    }
+}
+
+/// A candidate for the next track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Suggestion {
+    /// index into [`SortRequest::tracks`]
+    pub track: usize,
+    /// semitones the candidate has to be pitch-shifted by
+    pub shift: i8,
+    pub movement: Movement,
+    /// transition score from the current track, penalties included
+    pub weight: i32,
+    /// unplayed tracks still reachable after the candidate
+    pub reachable: usize,
+    /// `weight` plus the reachability bonus, what suggestions are ranked by
+    pub score: f32,
+}
+
+/// Ranks the unplayed tracks of `request.tracks` as the successor of
+/// `current`, after `played` (oldest first, `current` may be its last entry).
+///
+/// The request's constraints and history scoring apply as if `played` and
+/// `current` were the start of a sorted set.
+pub fn suggest_next(
+    request: &SortRequest,
+    current: usize,
+    played: &[usize],
+    options: &SuggestOptions,
+) -> Vec<Suggestion> {
+    let space = SearchSpace::new(request);
+    let mut history: Vec<usize> = played.iter().copied().filter(|&t| t != current).collect();
+    history.push(current);
+    let list: Vec<usize> = history
+        .iter()
+        .filter_map(|&track| space.track_node(track))
+        .collect();
+    let Some(current_node) = space.track_node(current) else {
+        return Vec::new();
+    };
+
+    let remaining = request
+        .tracks
+        .iter()
+        .enumerate()
+        .filter(|(index, track)| track.key().is_some() && !history.contains(index))
+        .count();
+
+    let mut best_by_track: HashMap<usize, Suggestion> = HashMap::new();
+    for pair in space.successors(current_node) {
+        let Some(weight) = space.step(&list, pair) else {
+            continue;
+        };
+        let node = space.node(pair.end);
+        let reachable = space.reachable_tracks(pair.end, &history);
+        let share = match remaining.saturating_sub(1) {
+            0 => 1.0,
+            others => reachable as f32 / others as f32,
+        };
+        let suggestion = Suggestion {
+            track: node.track,
+            shift: node.shift,
+            movement: pair.movement,
+            weight,
+            reachable,
+            score: weight as f32 + options.reachability_weight as f32 * share,
+        };
+        debug!(
+            "suggest_next: track={} weight={} reachable={}",
+            node.track, weight, reachable
+        );
+        best_by_track
+            .entry(node.track)
+            .and_modify(|best| {
+                if suggestion.score > best.score {
+                    *best = suggestion;
+                }
+            })
+            .or_insert(suggestion);
+    }
+
+    let mut suggestions: Vec<Suggestion> = best_by_track.into_values().collect();
+    suggestions.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.track.cmp(&b.track))
+    });
+    suggestions.truncate(options.count);
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::MovementWeights;
+    use crate::types::track::Track;
+
+    #[test]
+    fn prefers_candidates_that_keep_the_library_reachable() {
+        let tracks = vec![
+            Track::from_pair("now", "8A"),
+            Track::from_pair("dead end", "7B"),
+            Track::from_pair("bridge", "11B"),
+            Track::from_pair("next", "12B"),
+            Track::from_pair("played", "7A"),
+        ];
+        let weights = MovementWeights::default();
+        let request = SortRequest::new(&tracks, &weights);
+        let options = SuggestOptions {
+            count: 5,
+            reachability_weight: 40,
+        };
+
+        let suggestions = suggest_next(&request, 0, &[4], &options);
+        let ranked: Vec<usize> = suggestions.iter().map(|s| s.track).collect();
+        assert_eq!(ranked, vec![2, 1]);
+        assert_eq!(suggestions[0].movement, Movement::MoodBoost);
+        // 11B -> 12B -> 7B still reaches the dead end, 7B reaches nothing
+        assert_eq!(suggestions[0].reachable, 2);
+        assert_eq!(suggestions[1].movement, Movement::SubDomKey);
+        assert_eq!(suggestions[1].reachable, 0);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::types::key::Key;
+use crate::types::track::Track;
+
+/// How playing a track at another tempo without key-lock moves its pitch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoShift {
+    /// tempo the track is actually played at, `target_bpm` scaled to the
+    /// closest half or double time of the track
+    pub played_bpm: f32,
+    /// exact pitch change in semitones
+    pub semitones: f32,
+    /// pitch change rounded to whole semitones, the shift applied to the key
+    pub key_shift: i8,
+    /// what is left after rounding, between -50 and +50 cents
+    pub cents: f32,
+}
+
+impl TempoShift {
+    /// The pitch change of a track at `bpm` played at `target_bpm`.
+    ///
+    /// Panics unless both tempos are positive and finite, see
+    /// [`TempoShift::of`] for unchecked ones.
+    pub fn new(bpm: f32, target_bpm: f32) -> Self {
+        assert!(
+            is_tempo(bpm) && is_tempo(target_bpm),
+            "invalid tempo {bpm} at {target_bpm} BPM"
+        );
+        let mut played_bpm = target_bpm;
+        // a 87 BPM track in a 174 BPM set is played half time, not sped up
+        while played_bpm / bpm > 4.0 / 3.0 {
+            played_bpm /= 2.0;
+        }
+        while played_bpm / bpm < 2.0 / 3.0 {
+            played_bpm *= 2.0;
+        }
+        let semitones = 12.0 * (played_bpm / bpm).log2();
+        let key_shift = semitones.round();
+        Self {
+            played_bpm,
+            semitones,
+            key_shift: key_shift as i8,
+            cents: (semitones - key_shift) * 100.0,
+        }
+    }
+
+    /// `track`'s shift at `target_bpm`, if its tempo is known and both
+    /// tempos are positive and finite.
+    pub fn of(track: &Track, target_bpm: f32) -> Option<Self> {
+        let bpm = track.bpm()?;
+        (is_tempo(bpm) && is_tempo(target_bpm)).then(|| Self::new(bpm, target_bpm))
+    }
+}
+
+fn is_tempo(bpm: f32) -> bool {
+    bpm.is_finite() && bpm > 0.0
+}
+
+/// The key `track` sounds in when played at `target_bpm` without key-lock,
+/// or its own key when its tempo is unknown.
+pub fn effective_key(track: &Track, target_bpm: f32) -> Option<Key> {
+    let key = track.key()?;
+    match TempoShift::of(track, target_bpm) {
+        Some(shift) => Some(key.transpose(shift.key_shift)),
+        None => Some(*key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speeding_up_six_percent_moves_a_semitone() {
+        let shift = TempoShift::new(170.0, 174.0);
+        assert_eq!(shift.key_shift, 0);
+        assert!((shift.cents - 40.3).abs() < 0.1);
+
+        let shift = TempoShift::new(164.0, 174.0);
+        assert_eq!(shift.key_shift, 1);
+        let track = Track::from_pair("a", "8A").with_bpm(164.0);
+        assert_eq!(effective_key(&track, 174.0), Key::from_camelot("3A").ok());
+    }
+
+    #[test]
+    fn half_time_tracks_are_not_doubled() {
+        let shift = TempoShift::new(87.0, 174.0);
+        assert_eq!(shift.played_bpm, 87.0);
+        assert_eq!(shift.key_shift, 0);
+        assert_eq!(shift.cents, 0.0);
+    }
+
+    #[test]
+    fn invalid_tempos_shift_nothing() {
+        let track = Track::from_pair("a", "8A").with_bpm(174.0);
+        for target_bpm in [0.0, -174.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(TempoShift::of(&track, target_bpm), None);
+            assert_eq!(
+                effective_key(&track, target_bpm),
+                Key::from_camelot("8A").ok()
+            );
+        }
+        let track = Track::from_pair("b", "8A").with_bpm(0.0);
+        assert_eq!(TempoShift::of(&track, 174.0), None);
+    }
+}
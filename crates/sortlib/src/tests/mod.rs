@@ -1,2 +1,25 @@
 #[test]
 fn get_key() {}
+
+use crate::types::key::{Key, KeyLetter};
+
+#[test]
+fn pitch_class_round_trips() {
+    for number in 1..=12 {
+        for letter in [KeyLetter::A, KeyLetter::B] {
+            let key = Key::new(number, letter).unwrap();
+            assert_eq!(Key::from_pitch_class(key.pitch_class(), letter), key);
+        }
+    }
+    assert_eq!(Key::from_camelot("8B").unwrap().pitch_class(), 0);
+    assert_eq!(Key::from_camelot("8A").unwrap().pitch_class(), 9);
+}
+
+#[test]
+fn transpose_moves_seven_steps_per_semitone() {
+    let key = Key::from_camelot("8A").unwrap();
+    assert_eq!(key.transpose(1), Key::from_camelot("3A").unwrap());
+    assert_eq!(key.transpose(-1), Key::from_camelot("1A").unwrap());
+    assert_eq!(key.transpose(2), Key::from_camelot("10A").unwrap());
+    assert_eq!(key.transpose(12), key);
+}
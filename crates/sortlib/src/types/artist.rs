@@ -0,0 +1,108 @@
+/// Separators between artists in a credit, matched case-insensitively with
+/// the surrounding spaces.
+const SEPARATORS: [&str; 9] = [
+    " feat. ",
+    " feat ",
+    " ft. ",
+    " ft ",
+    " featuring ",
+    " vs. ",
+    " vs ",
+    " x ",
+    " & ",
+];
+
+/// Splits a credit such as `"Pythius, Dj Hidden feat. Flowanastasia"` into
+/// the single artist names.
+pub fn split_credit(credit: &str) -> Vec<String> {
+    let mut parts = vec![credit.replace(',', " & ")];
+    for separator in SEPARATORS {
+        parts = parts
+            .iter()
+            .flat_map(|part| split_ignore_case(part, separator))
+            .collect();
+    }
+    parts
+        .into_iter()
+        .map(|part| part.trim().to_string())
+        .filter(|part| !part.is_empty())
+        .collect()
+}
+
+/// Artists credited in brackets of a title, such as `"Temple (Ekwols Remix)"`
+/// or `"Chain Reaction (feat. Coppa)"`.
+pub fn credited_in_title(title: &str) -> Vec<String> {
+    const PREFIXES: [&str; 4] = ["feat. ", "feat ", "ft. ", "ft "];
+    const SUFFIXES: [&str; 4] = [" remix", " vip mix", " edit", " bootleg"];
+
+    let mut artists = Vec::new();
+    for group in title.split(['(', '[']).skip(1) {
+        let inner = group.split([')', ']']).next().unwrap_or_default().trim();
+        let lower = inner.to_lowercase();
+        let credit = if let Some(prefix) = PREFIXES.iter().find(|p| lower.starts_with(*p)) {
+            inner.get(prefix.len()..)
+        } else if let Some(suffix) = SUFFIXES.iter().find(|s| lower.ends_with(*s)) {
+            inner.get(..inner.len().saturating_sub(suffix.len()))
+        } else {
+            None
+        };
+        if let Some(credit) = credit {
+            artists.extend(split_credit(credit));
+        }
+    }
+    artists
+}
+
+/// Canonical form used to compare artist names.
+pub fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn split_ignore_case(value: &str, separator: &str) -> Vec<String> {
+    let lower = value.to_lowercase();
+    // lowercasing keeps byte offsets for the ASCII separators we look for only
+    // when the value itself is ASCII
+    if lower.len() != value.len() {
+        return vec![value.to_string()];
+    }
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while let Some(offset) = lower[start..].find(separator) {
+        parts.push(value[start..start + offset].to_string());
+        start += offset + separator.len();
+    }
+    parts.push(value[start..].to_string());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_multi_artist_credits() {
+        assert_eq!(
+            split_credit("Pythius, Dj Hidden feat. Flowanastasia"),
+            vec!["Pythius", "Dj Hidden", "Flowanastasia"]
+        );
+        assert_eq!(
+            split_credit("Tobax x Mean Teeth"),
+            vec!["Tobax", "Mean Teeth"]
+        );
+        assert_eq!(split_credit("Burr Oak"), vec!["Burr Oak"]);
+    }
+
+    #[test]
+    fn finds_remixers_and_features_in_titles() {
+        assert_eq!(credited_in_title("Temple (Ekwols Remix)"), vec!["Ekwols"]);
+        assert_eq!(
+            credited_in_title("Chain Reaction (feat. Coppa)"),
+            vec!["Coppa"]
+        );
+        assert!(credited_in_title("Lullaby (Original Mix)").is_empty());
+        assert_eq!(normalize("  Burr   OAK "), "burr oak");
+    }
+}
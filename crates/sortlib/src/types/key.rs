@@ -66,6 +66,37 @@ impl Key {
     pub fn letter(&self) -> KeyLetter {
         self.letter
     }
+
+    /// The key whose tonic has pitch class `pitch_class` (C = 0) in the mode
+    /// given by `letter`.
+    pub fn from_pitch_class(pitch_class: u8, letter: KeyLetter) -> Self {
+        let from_c = match letter {
+            KeyLetter::B => pitch_class % 12,
+            KeyLetter::A => (pitch_class % 12 + 3) % 12,
+        };
+        // inverse of `pitch_class`: 7 is its own inverse modulo 12
+        let number = (7 * from_c as u16 + 8) % 12;
+        Self {
+            number: if number == 0 { 12 } else { number as u8 },
+            letter,
+        }
+    }
+
+    /// The same mode with the tonic moved by `semitones`.
+    pub fn transpose(&self, semitones: i8) -> Self {
+        let pitch_class = (self.pitch_class() as i16 + semitones as i16).rem_euclid(12);
+        Self::from_pitch_class(pitch_class as u8, self.letter)
+    }
+
+    /// Pitch class of the tonic, with C = 0 and A = 9.
+    pub fn pitch_class(&self) -> u8 {
+        // one step on the wheel is a fifth (7 semitones); 8B is C major, 8A is A minor
+        let from_c = (7 * (self.number as u16 + 4) % 12) as u8;
+        match self.letter {
+            KeyLetter::B => from_c,
+            KeyLetter::A => (from_c + 9) % 12,
+        }
+    }
 }
 
 impl fmt::Display for Key {
@@ -1,2 +1,3 @@
+pub mod artist;
 pub mod key;
 pub mod track;
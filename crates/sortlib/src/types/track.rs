@@ -1,3 +1,4 @@
+use crate::types::artist;
 use crate::types::key::Key;
 
 /// A struct representing a track that is stored on the computer
@@ -11,6 +12,24 @@ pub struct Track {
     path: std::path::PathBuf,
     /// (melodic) key of the track
     key: Option<Key>,
+    /// how sure the key detection was about `key`, between 0 and 1
+    key_confidence: Option<f32>,
+    /// key of the opening seconds, when it differs from `key`
+    intro_key: Option<Key>,
+    /// key of the closing seconds, when it differs from `key`
+    outro_key: Option<Key>,
+    /// original tempo of the track in beats per minute
+    bpm: Option<f32>,
+    /// length of the track in seconds
+    duration: Option<f32>,
+    /// perceived intensity between 0 and 1, e.g. from a DJ library
+    energy: Option<f32>,
+    /// average loudness in dBFS
+    loudness: Option<f32>,
+    /// genres the track is tagged with
+    genres: Vec<String>,
+    /// every artist credited on the track, including featured artists and remixers
+    artists: Vec<String>,
 }
 
 impl Track {
@@ -25,6 +44,15 @@ impl Track {
             name: name.into(),
             path: path.into(),
             key,
+            key_confidence: None,
+            intro_key: None,
+            outro_key: None,
+            bpm: None,
+            duration: None,
+            energy: None,
+            loudness: None,
+            genres: Vec::new(),
+            artists: Vec::new(),
         }
     }
     pub fn from_pair(name: &str, key: &str) -> Self {
@@ -33,6 +61,66 @@ impl Track {
         Track::new(None, name.to_string(), path, Some(t_key))
     }
 
+    pub fn with_key_confidence(mut self, confidence: f32) -> Self {
+        self.key_confidence = Some(confidence);
+        self
+    }
+
+    pub fn with_intro_key(mut self, key: Key) -> Self {
+        self.intro_key = Some(key);
+        self
+    }
+
+    pub fn with_outro_key(mut self, key: Key) -> Self {
+        self.outro_key = Some(key);
+        self
+    }
+
+    pub fn with_bpm(mut self, bpm: f32) -> Self {
+        self.bpm = Some(bpm);
+        self
+    }
+
+    pub fn with_duration(mut self, seconds: f32) -> Self {
+        self.duration = Some(seconds);
+        self
+    }
+
+    pub fn with_energy(mut self, energy: f32) -> Self {
+        self.energy = Some(energy);
+        self
+    }
+
+    pub fn with_loudness(mut self, loudness: f32) -> Self {
+        self.loudness = Some(loudness);
+        self
+    }
+
+    pub fn with_genres<S: Into<String>>(mut self, genres: impl IntoIterator<Item = S>) -> Self {
+        self.genres = genres.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_artists<S: Into<String>>(mut self, artists: impl IntoIterator<Item = S>) -> Self {
+        self.artists = artists.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the artists from an artist credit and the track title, picking up
+    /// `feat.` artists and remixers, e.g. `("Absu NTQL", "Temple (Ekwols Remix)")`.
+    pub fn with_credit(self, credit: &str, title: &str) -> Self {
+        let mut artists = artist::split_credit(credit);
+        for name in artist::credited_in_title(title) {
+            if !artists
+                .iter()
+                .any(|known| artist::normalize(known) == artist::normalize(&name))
+            {
+                artists.push(name);
+            }
+        }
+        self.with_artists(artists)
+    }
+
     pub fn id(&self) -> Option<i32> {
         self.id
     }
@@ -48,4 +136,55 @@ impl Track {
     pub fn key(&self) -> Option<&Key> {
         self.key.as_ref()
     }
+
+    /// Key the track is mixed in with, its global key unless the intro was
+    /// analyzed separately.
+    pub fn intro_key(&self) -> Option<&Key> {
+        self.intro_key.as_ref().or(self.key.as_ref())
+    }
+
+    /// Key the track is mixed out of, its global key unless the outro was
+    /// analyzed separately.
+    pub fn outro_key(&self) -> Option<&Key> {
+        self.outro_key.as_ref().or(self.key.as_ref())
+    }
+
+    pub fn key_confidence(&self) -> Option<f32> {
+        self.key_confidence
+    }
+
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    pub fn duration(&self) -> Option<f32> {
+        self.duration
+    }
+
+    pub fn energy(&self) -> Option<f32> {
+        self.energy
+    }
+
+    pub fn loudness(&self) -> Option<f32> {
+        self.loudness
+    }
+
+    pub fn genres(&self) -> &[String] {
+        &self.genres
+    }
+
+    pub fn artists(&self) -> &[String] {
+        &self.artists
+    }
+
+    /// Whether any artist is credited on both tracks.
+    pub fn shares_artist(&self, other: &Track) -> bool {
+        self.artists.iter().any(|name| {
+            let name = artist::normalize(name);
+            other
+                .artists
+                .iter()
+                .any(|other| artist::normalize(other) == name)
+        })
+    }
 }